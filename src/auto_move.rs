@@ -0,0 +1,409 @@
+use std::collections::{HashSet, VecDeque};
+
+use rltk::{Point, BaseMap, Algorithm2D, SmallVec};
+use specs::prelude::*;
+
+use crate::{Map, TileType, RunState, GameLog, LogEntry, CombatStats, Position, Monster, Item, Hidden, EntryTrigger, Name, try_move_player};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum AutoMoveMode{
+    /// head for the nearest tile that hasn't been seen yet
+    Explore,
+    Travel{ x: i32, y: i32 },
+    /// keep going in a direction, in map coordinates, following corridors around corners
+    Run{ dx: i32, dy: i32, in_corridor: bool },
+}
+
+const DIRECTIONS: [(i32, i32); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// Walking on the player's behalf, one step per turn, until they arrive or something needs their attention
+pub struct AutoMove{
+    pub mode: Option<AutoMoveMode>,
+    last_hp: i32,
+    last_position: Point,
+    turns_without_moving: i32,
+    /// monsters and items the player already knew about, anything new interrupts
+    seen: HashSet<Entity>,
+}
+
+impl Default for AutoMove{
+    fn default() -> Self {
+        AutoMove{ mode: None, last_hp: 0, last_position: Point::new(-1, -1), turns_without_moving: 0, seen: HashSet::new() }
+    }
+}
+
+fn player_hp(world: &World) -> i32{
+    let player_entity = world.fetch::<Entity>();
+    world.read_storage::<CombatStats>().get(*player_entity)
+        .map(|stats| stats.hp)
+        .unwrap_or(0)
+}
+
+/// Monsters and items on tiles the player can currently see
+fn visible_interests(world: &World) -> HashSet<Entity>{
+    let map = world.fetch::<Map>();
+    let entities = world.entities();
+    let positions = world.read_storage::<Position>();
+    let monsters = world.read_storage::<Monster>();
+    let items = world.read_storage::<Item>();
+    let hidden = world.read_storage::<Hidden>();
+
+    (&entities, &positions, !&hidden).join()
+        .filter(|(entity, _pos, _hidden)| monsters.get(*entity).is_some() || items.get(*entity).is_some())
+        .filter(|(_entity, pos, _hidden)| map.is_idx_valid(pos.x, pos.y) && map.currently_visible_tiles[map.xy_idx(pos.x, pos.y)])
+        .map(|(entity, _pos, _hidden)| entity)
+        .collect()
+}
+
+/// Start walking. Exploring is refused while a monster is in view
+pub fn start_auto_move(world: &mut World, mode: AutoMoveMode) -> RunState{
+    let seen = visible_interests(world);
+
+    if mode == AutoMoveMode::Explore && seen.iter().any(|entity| world.read_storage::<Monster>().get(*entity).is_some()){
+        world.write_resource::<GameLog>().add(LogEntry::new().warning("Not with enemies in view."));
+        return RunState::AwaitingInput;
+    }
+
+    if let AutoMoveMode::Travel{ x, y } = mode{
+        let map = world.fetch::<Map>();
+        if !map.is_idx_valid(x, y) || !map.revealed_tiles[map.xy_idx(x, y)]{
+            drop(map);
+            world.write_resource::<GameLog>().log("You don't know the way there.");
+            return RunState::AwaitingInput;
+        }
+    }
+
+    begin_auto_move(world, mode, seen);
+    auto_move_step(world)
+}
+
+fn begin_auto_move(world: &mut World, mode: AutoMoveMode, seen: HashSet<Entity>){
+    let last_hp = player_hp(world);
+    *world.write_resource::<AutoMove>() = AutoMove{ mode: Some(mode), last_hp, seen, ..AutoMove::default() };
+}
+
+/// Run in a direction given in map coordinates. If the way is blocked it's a normal move,
+/// so running into a monster or door attacks or opens it once
+pub fn start_run(world: &mut World, dx: i32, dy: i32) -> RunState{
+    let player_position = *world.fetch::<Point>();
+    let way_is_clear = {
+        let map = world.fetch::<Map>();
+        let (x, y) = (player_position.x + dx, player_position.y + dy);
+        map.is_idx_valid(x, y)
+            && map.tiles[map.xy_idx(x, y)] != TileType::Door
+            && is_walkable(&map, &known_traps(world, &map), map.xy_idx(x, y))
+    };
+
+    if way_is_clear{
+        let seen = visible_interests(world);
+        begin_auto_move(world, AutoMoveMode::Run{ dx, dy, in_corridor: false }, seen);
+    }
+
+    // try_move_player counts y upwards
+    try_move_player(dx, -dy, world)
+}
+
+/// Travel to the nearest down stairs the player has found
+pub fn travel_to_stairs(world: &mut World) -> RunState{
+    let player_position = *world.fetch::<Point>();
+    let stairs = {
+        let map = world.fetch::<Map>();
+        map.tiles.iter().enumerate()
+            .filter(|(idx, tile)| **tile == TileType::DownStairs && map.revealed_tiles[*idx])
+            .map(|(idx, _tile)| Point::new(idx as i32 % map.width, idx as i32 / map.width))
+            .min_by_key(|stairs| (stairs.x - player_position.x).abs() + (stairs.y - player_position.y).abs())
+    };
+
+    match stairs{
+        None => {
+            world.write_resource::<GameLog>().log("You haven't found the way down yet.");
+            RunState::AwaitingInput
+        },
+        Some(stairs) => start_auto_move(world, AutoMoveMode::Travel{ x: stairs.x, y: stairs.y }),
+    }
+}
+
+/// Why the walk should stop this turn, if it should
+fn interruption(world: &World) -> Option<LogEntry>{
+    let hp = player_hp(world);
+    let visible = visible_interests(world);
+    let player_position = *world.fetch::<Point>();
+    let mut auto_move = world.write_resource::<AutoMove>();
+
+    let was_hurt = hp < auto_move.last_hp;
+    auto_move.last_hp = hp;
+
+    let newly_seen = visible.difference(&auto_move.seen).next().copied();
+    auto_move.seen = visible;
+
+    if player_position == auto_move.last_position{
+        auto_move.turns_without_moving += 1;
+    } else {
+        auto_move.turns_without_moving = 0;
+    }
+    auto_move.last_position = player_position;
+
+    if was_hurt{
+        return Some(LogEntry::new().warning("You stop, you are hurt!"));
+    }
+
+    if let Some(entity) = newly_seen{
+        let name = world.read_storage::<Name>().get(entity)
+            .map(|name| name.name.clone())
+            .unwrap_or_else(|| "something".to_string());
+        return Some(LogEntry::new().text("You stop, you see ").name(name).text("."));
+    }
+
+    // opening a door takes a turn without moving, anything longer means the way is blocked
+    if auto_move.turns_without_moving >= 2{
+        return Some(LogEntry::new().text("You stop, something is in the way."));
+    }
+
+    None
+}
+
+/// Tiles the player knows are safe to walk over. Closed doors count, walking into them opens them
+fn is_walkable(map: &Map, known_traps: &[usize], idx: usize) -> bool{
+    if !map.revealed_tiles[idx] || map.fire[idx] > 0 || map.gas[idx] > 0{
+        return false;
+    }
+
+    if matches!(map.tiles[idx], TileType::Wall | TileType::LockedDoor | TileType::DeepWater | TileType::Lava){
+        return false;
+    }
+
+    if map.blocked_tiles[idx] && map.tiles[idx] != TileType::Door{
+        return false;
+    }
+
+    // steer around traps the player has found
+    !known_traps.contains(&idx)
+}
+
+/// Tiles holding traps the player has found
+fn known_traps(world: &World, map: &Map) -> Vec<usize>{
+    (&world.read_storage::<Position>(), &world.read_storage::<EntryTrigger>(), !&world.read_storage::<Hidden>()).join()
+        .filter(|(pos, _trigger, _hidden)| map.is_idx_valid(pos.x, pos.y))
+        .map(|(pos, _trigger, _hidden)| map.xy_idx(pos.x, pos.y))
+        .collect()
+}
+
+/// The map as far as the player knows it, so routes don't give away unexplored ground
+struct KnownMap<'a>{
+    map: &'a Map,
+    known_traps: Vec<usize>,
+}
+
+impl BaseMap for KnownMap<'_>{
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.map.is_opaque(idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        let mut exits = SmallVec::new();
+        let x = idx as i32 % self.map.width;
+        let y = idx as i32 / self.map.width;
+
+        for (dx, dy) in DIRECTIONS{
+            if !self.map.is_idx_valid(x + dx, y + dy){
+                continue;
+            }
+
+            let exit_idx = self.map.xy_idx(x + dx, y + dy);
+            if is_walkable(self.map, &self.known_traps, exit_idx){
+                let base = if dx != 0 && dy != 0 { 1.45 } else { 1.0 };
+                exits.push((exit_idx, base * self.map.tiles[exit_idx].movement_cost()));
+            }
+        }
+
+        exits
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+}
+
+impl Algorithm2D for KnownMap<'_>{
+    fn dimensions(&self) -> Point {
+        self.map.dimensions()
+    }
+}
+
+/// A* route over known ground from the player to the destination, not counting the tile they stand on.
+/// Empty if there is no known way there
+pub fn travel_route(world: &World, destination: Point) -> Vec<Point>{
+    let map = world.fetch::<Map>();
+    let player_position = *world.fetch::<Point>();
+    if !map.is_idx_valid(destination.x, destination.y) || destination == player_position{
+        return Vec::new();
+    }
+
+    let known_map = KnownMap{ map: &map, known_traps: known_traps(world, &map) };
+    let path = rltk::a_star_search(
+        map.xy_idx(player_position.x, player_position.y),
+        map.xy_idx(destination.x, destination.y),
+        &known_map);
+
+    if !path.success{
+        return Vec::new();
+    }
+
+    path.steps.iter()
+        .skip(1)
+        .map(|idx| Point::new(*idx as i32 % map.width, *idx as i32 / map.width))
+        .collect()
+}
+
+/// First step of the shortest known route from start to the nearest tile passing is_goal
+fn next_step(world: &World, start: Point, is_goal: impl Fn(&Map, usize) -> bool) -> Option<Point>{
+    let map = world.fetch::<Map>();
+    let known_traps = known_traps(world, &map);
+    let start_idx = map.xy_idx(start.x, start.y);
+    let mut came_from: Vec<Option<usize>> = vec![None; map.tiles.len()];
+    let mut frontier = VecDeque::from([start_idx]);
+    came_from[start_idx] = Some(start_idx);
+
+    while let Some(idx) = frontier.pop_front(){
+        if idx != start_idx && is_goal(&map, idx){
+            // walk back until the tile right next to the start
+            let mut step = idx;
+            while let Some(previous) = came_from[step]{
+                if previous == start_idx{
+                    break;
+                }
+                step = previous;
+            }
+            return Some(Point::new(step as i32 % map.width, step as i32 / map.width));
+        }
+
+        let x = idx as i32 % map.width;
+        let y = idx as i32 / map.width;
+        for (dx, dy) in DIRECTIONS{
+            if !map.is_idx_valid(x + dx, y + dy){
+                continue;
+            }
+
+            let next_idx = map.xy_idx(x + dx, y + dy);
+            if came_from[next_idx].is_none() && is_walkable(&map, &known_traps, next_idx){
+                came_from[next_idx] = Some(idx);
+                frontier.push_back(next_idx);
+            }
+        }
+    }
+
+    None
+}
+
+/// A revealed tile with unrevealed ground next to it
+fn is_unexplored_edge(map: &Map, idx: usize) -> bool{
+    let x = idx as i32 % map.width;
+    let y = idx as i32 / map.width;
+    [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+        .any(|(dx, dy)| map.is_idx_valid(x + dx, y + dy) && !map.revealed_tiles[map.xy_idx(x + dx, y + dy)])
+}
+
+/// Split tiles into groups that touch each other, each group is one way onwards
+fn touching_groups(tiles: &[Point]) -> Vec<Vec<Point>>{
+    let mut groups: Vec<Vec<Point>> = Vec::new();
+
+    for tile in tiles{
+        let touches = |other: &Point| (other.x - tile.x).abs() <= 1 && (other.y - tile.y).abs() <= 1;
+        let (touching, apart): (Vec<Vec<Point>>, Vec<Vec<Point>>) = groups.into_iter()
+            .partition(|group| group.iter().any(touches));
+
+        let mut merged: Vec<Point> = touching.into_iter().flatten().collect();
+        merged.push(*tile);
+        groups = apart;
+        groups.push(merged);
+    }
+
+    groups
+}
+
+/// Where a run goes next and how it carries on. Corridors are followed around corners, open ground is
+/// crossed in a straight line. None when there is something to stop for: stairs, items, doors,
+/// a junction, a dead end or the mouth of a corridor opening into a room
+fn run_step(world: &World, position: Point, dx: i32, dy: i32, in_corridor: bool) -> Option<(Point, AutoMoveMode)>{
+    let map = world.fetch::<Map>();
+    let items = world.read_storage::<Item>();
+    let idx = map.xy_idx(position.x, position.y);
+
+    if matches!(map.tiles[idx], TileType::DownStairs | TileType::UpStairs | TileType::Door | TileType::OpenDoor){
+        return None;
+    }
+    if map.tile_content[idx].iter().any(|entity| items.get(*entity).is_some()){
+        return None;
+    }
+
+    let neighbours: Vec<Point> = DIRECTIONS.iter()
+        .map(|(nx, ny)| Point::new(position.x + nx, position.y + ny))
+        .filter(|neighbour| map.is_idx_valid(neighbour.x, neighbour.y))
+        .collect();
+    if neighbours.iter().any(|neighbour| map.tiles[map.xy_idx(neighbour.x, neighbour.y)] == TileType::Door){
+        return None;
+    }
+
+    let known_traps = known_traps(world, &map);
+    let came_from = Point::new(position.x - dx, position.y - dy);
+    let open: Vec<Point> = neighbours.into_iter()
+        .filter(|neighbour| *neighbour != came_from && is_walkable(&map, &known_traps, map.xy_idx(neighbour.x, neighbour.y)))
+        .collect();
+    let ahead = Point::new(position.x + dx, position.y + dy);
+
+    match touching_groups(&open).as_slice(){
+        // a corridor, carry on straight if possible otherwise take the turn
+        [way_on] if way_on.len() <= 2 => {
+            let next = if way_on.contains(&ahead){
+                ahead
+            } else {
+                *way_on.iter().find(|tile| tile.x == position.x || tile.y == position.y).unwrap_or(&way_on[0])
+            };
+            Some((next, AutoMoveMode::Run{ dx: next.x - position.x, dy: next.y - position.y, in_corridor: true }))
+        },
+        [_open_ground] if !in_corridor && open.contains(&ahead) => Some((ahead, AutoMoveMode::Run{ dx, dy, in_corridor: false })),
+        _ => None,
+    }
+}
+
+/// Take the next step of the walk, or stop and hand control back to the player
+pub fn auto_move_step(world: &mut World) -> RunState{
+    let mode = match world.fetch::<AutoMove>().mode{
+        None => return RunState::AwaitingInput,
+        Some(mode) => mode,
+    };
+
+    if let Some(reason) = interruption(world){
+        world.write_resource::<AutoMove>().mode = None;
+        world.write_resource::<GameLog>().add(reason);
+        return RunState::AwaitingInput;
+    }
+
+    let player_position = *world.fetch::<Point>();
+    let step = match mode{
+        AutoMoveMode::Explore => next_step(world, player_position, |map, idx| map.revealed_tiles[idx] && is_unexplored_edge(map, idx)),
+        AutoMoveMode::Travel{ x, y } => travel_route(world, Point::new(x, y)).first().copied(),
+        AutoMoveMode::Run{ dx, dy, in_corridor } => {
+            let next = run_step(world, player_position, dx, dy, in_corridor);
+            if let Some((_step, next_mode)) = next{
+                world.write_resource::<AutoMove>().mode = Some(next_mode);
+            }
+            next.map(|(step, _next_mode)| step)
+        },
+    };
+
+    match step{
+        None => {
+            world.write_resource::<AutoMove>().mode = None;
+            match mode{
+                AutoMoveMode::Explore => world.write_resource::<GameLog>().log("Nothing left to explore."),
+                AutoMoveMode::Travel{ x, y } if x != player_position.x || y != player_position.y =>
+                    world.write_resource::<GameLog>().log("You don't know the way there."),
+                _ => {},
+            }
+            RunState::AwaitingInput
+        },
+        // try_move_player counts y upwards
+        Some(step) => try_move_player(step.x - player_position.x, player_position.y - step.y, world),
+    }
+}
@@ -0,0 +1,42 @@
+use rltk::Point;
+use specs::prelude::*;
+
+use crate::Map;
+
+/// Size of the part of the screen the map is drawn in, the rest is gui
+pub const VIEWPORT_WIDTH: i32 = 80;
+pub const VIEWPORT_HEIGHT: i32 = 43;
+
+/// Map coordinate drawn in the top left corner of the screen.
+/// The camera keeps the player centered but stops at the map's edges so no space is wasted
+pub fn camera_origin(world: &World) -> Point{
+    let map = world.fetch::<Map>();
+    let player_position = world.fetch::<Point>();
+
+    let origin_x = player_position.x - VIEWPORT_WIDTH/2;
+    let origin_y = player_position.y - VIEWPORT_HEIGHT/2;
+
+    Point::new(
+        origin_x.clamp(0, i32::max(0, map.width - VIEWPORT_WIDTH)),
+        origin_y.clamp(0, i32::max(0, map.height - VIEWPORT_HEIGHT)),
+    )
+}
+
+/// Translate a screen position, like the mouse, into the map position under it
+pub fn screen_to_map(world: &World, screen_point: Point) -> Point{
+    let origin = camera_origin(world);
+    Point::new(screen_point.x + origin.x, screen_point.y + origin.y)
+}
+
+/// Translate a map position to the screen. None if the camera can't currently see it
+pub fn map_to_screen(world: &World, map_point: Point) -> Option<Point>{
+    let origin = camera_origin(world);
+    let screen_x = map_point.x - origin.x;
+    let screen_y = map_point.y - origin.y;
+
+    if !(0..VIEWPORT_WIDTH).contains(&screen_x) || !(0..VIEWPORT_HEIGHT).contains(&screen_y){
+        return None;
+    }
+
+    Some(Point::new(screen_x, screen_y))
+}
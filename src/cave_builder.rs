@@ -0,0 +1,135 @@
+use rltk::{RandomNumberGenerator, DijkstraMap};
+
+use crate::{Map, TileType, Rect};
+
+/// Size of the chunks an open level is split into so the spawner has rooms to fill
+const SPAWN_AREA_SIZE: i32 = 12;
+
+/// Natural caverns grown with a cellular automaton
+pub fn cave_level(map_depth: i32, width: i32, height: i32) -> Map{
+    let mut rng = RandomNumberGenerator::new();
+    let mut map = Map::new(map_depth, width, height);
+
+    grow_caverns(&mut map, &mut rng, 55);
+    finish_open_level(&mut map);
+    map
+}
+
+/// Open woodland, the walls are trees and most of the ground is grass
+pub fn forest_level(map_depth: i32, width: i32, height: i32) -> Map{
+    let mut rng = RandomNumberGenerator::new();
+    let mut map = Map::new(map_depth, width, height);
+
+    grow_caverns(&mut map, &mut rng, 65);
+    for tile in map.tiles.iter_mut(){
+        if *tile == TileType::Floor{
+            *tile = match rng.roll_dice(1, 10){
+                1..=5 => TileType::Grass,
+                6..=7 => TileType::TallGrass,
+                _ => TileType::Floor,
+            };
+        }
+    }
+    finish_open_level(&mut map);
+    map
+}
+
+/// Fill the map with random noise then smooth it out into caverns.
+/// floor_percent is the chance each tile starts as floor, higher is more open
+fn grow_caverns(map: &mut Map, rng: &mut RandomNumberGenerator, floor_percent: i32){
+    for y in 1..map.height-1{
+        for x in 1..map.width-1{
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = if rng.roll_dice(1, 100) <= floor_percent { TileType::Floor } else { TileType::Wall };
+        }
+    }
+
+    for _ in 0..12{
+        let mut next_tiles = map.tiles.clone();
+
+        for y in 1..map.height-1{
+            for x in 1..map.width-1{
+                let mut neighbour_walls = 0;
+                for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)]{
+                    if map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall{
+                        neighbour_walls += 1;
+                    }
+                }
+
+                let idx = map.xy_idx(x, y);
+                next_tiles[idx] = if neighbour_walls > 4 || neighbour_walls == 0 { TileType::Wall } else { TileType::Floor };
+            }
+        }
+
+        map.tiles = next_tiles;
+    }
+}
+
+/// Pick the start, wall off anything the player can't reach, put the stairs as far away
+/// as possible and split the level into spawn areas
+fn finish_open_level(map: &mut Map){
+    // start on the open tile closest to the middle of the left edge
+    let mut start = (map.width/2, map.height/2);
+    'search: for x in 1..map.width-1{
+        for offset in 0..map.height/2{
+            for y in [map.height/2 - offset, map.height/2 + offset]{
+                if map.is_idx_valid(x, y) && map.tiles[map.xy_idx(x, y)] != TileType::Wall{
+                    start = (x, y);
+                    break 'search;
+                }
+            }
+        }
+    }
+    map.starting_position = start;
+    let start_idx = map.xy_idx(start.0, start.1);
+
+    let mut reachable = vec![false; map.tiles.len()];
+    for idx in map.reachable_tiles(start_idx){
+        reachable[idx] = true;
+    }
+    for (idx, tile) in map.tiles.iter_mut().enumerate(){
+        if !reachable[idx]{
+            *tile = TileType::Wall;
+        }
+    }
+
+    map.populate_blocked_tiles();
+    let distances = DijkstraMap::new(map.width, map.height, &[start_idx], &*map, 2000.0);
+    let mut stairs_idx = start_idx;
+    for (idx, distance) in distances.map.iter().enumerate(){
+        if *distance < f32::MAX && *distance > distances.map[stairs_idx]{
+            stairs_idx = idx;
+        }
+    }
+    map.tiles[stairs_idx] = TileType::DownStairs;
+
+    // the area holding the player goes first, the spawner leaves it empty
+    for y in (1..map.height-1).step_by(SPAWN_AREA_SIZE as usize){
+        for x in (1..map.width-1).step_by(SPAWN_AREA_SIZE as usize){
+            let area = Rect::new(
+                x, y,
+                i32::min(SPAWN_AREA_SIZE, map.width-2-x) - 1,
+                i32::min(SPAWN_AREA_SIZE, map.height-2-y) - 1,
+            );
+
+            let holds_start = start.0 >= area.x1 && start.0 <= area.x2 && start.1 >= area.y1 && start.1 <= area.y2;
+            if holds_start{
+                map.rooms.insert(0, area);
+            } else if count_open_tiles(map, &area) >= 10{
+                map.rooms.push(area);
+            }
+        }
+    }
+}
+
+fn count_open_tiles(map: &Map, area: &Rect) -> usize{
+    let mut count = 0;
+    for y in area.y1..=area.y2{
+        for x in area.x1..=area.x2{
+            if map.tiles[map.xy_idx(x, y)] != TileType::Wall{
+                count += 1;
+            }
+        }
+    }
+    count
+}
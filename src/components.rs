@@ -5,7 +5,7 @@ use serde::{Serialize, Deserialize};
 use specs::saveload::*;
 use specs::error::*;
 
-use crate::Map;
+use crate::{Map, RunStats, VisitedLevels};
 
 #[derive(Component, ConvertSaveload, Clone)] // Creates Vector storage of Self objs
 pub struct Position{
@@ -13,6 +13,14 @@ pub struct Position{
     pub y: i32,
 }
 
+/// Takes the place of Position for anything on a level the player has left
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct OtherLevelPosition{
+    pub x: i32,
+    pub y: i32,
+    pub depth: i32,
+}
+
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct Renderable{
     pub symbol: rltk::FontCharType,
@@ -61,18 +69,58 @@ pub struct WantsToMelee {
     pub target : Entity
 }
 
-#[derive(Component, Debug, Clone, ConvertSaveload)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DamageType{
+    Physical,
+    /// blunt blows from maces and the like, what skeletons can't stand
+    Bludgeoning,
+    Fire,
+    Cold,
+    Poison,
+    Psychic,
+}
+
+impl DamageType{
+    pub fn name(&self) -> &'static str{
+        match self{
+            DamageType::Physical => "physical",
+            DamageType::Bludgeoning => "bludgeoning",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Poison => "poison",
+            DamageType::Psychic => "psychic",
+        }
+    }
+}
+
+/// One hit: how much, what kind and where it came from
+#[derive(Debug, Clone)]
+pub struct DamageInstance{
+    pub amount: i32,
+    pub damage_type: DamageType,
+    /// The creature or trap that dealt it, if anything did
+    pub source: Option<Entity>,
+    /// The weapon, item, spell or ability it was dealt with
+    pub item: Option<Entity>,
+    /// How a death message names it, kept as text since the source may be gone by then
+    pub cause: String,
+    /// Logged as the attacker hitting rather than the item
+    pub melee: bool,
+}
+
+/// Hits waiting to be applied. Not saved, the damage system empties it every time it runs
+#[derive(Component, Debug, Clone)]
 pub struct SuffersDamage{
-    pub amount: Vec<i32>,
+    pub instances: Vec<DamageInstance>,
 }
 
 impl SuffersDamage{
-    pub fn new_damage(suffers_damage: &mut WriteStorage<SuffersDamage>, entity: Entity, amount: i32){
+    pub fn new_damage(suffers_damage: &mut WriteStorage<SuffersDamage>, entity: Entity, instance: DamageInstance){
         // if the entity already has a suffers damage component, simply add the dmg suffered
         if let Some(damaged_entity) = suffers_damage.get_mut(entity){
-            damaged_entity.amount.push(amount);
+            damaged_entity.instances.push(instance);
         } else { // if the entity does not have a suffers damage component, initialize it and add the dmg suffered
-            let suffered_damage = SuffersDamage{ amount: vec![amount] };
+            let suffered_damage = SuffersDamage{ instances: vec![instance] };
             suffers_damage.insert(entity, suffered_damage)
                 .expect("Unable to isert damage.");
         }
@@ -122,6 +170,46 @@ pub struct Ranged{
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct InflictsDamage{
     pub damage: i32,
+    pub damage_type: DamageType,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// What kind of damage a creature's attacks do, or a weapon's when it is wielded. Physical without one
+pub struct MeleeDamageType{
+    pub damage_type: DamageType,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Halves damage of the resisted types and ignores the immune ones. Armour passes it on to whoever wears it
+pub struct Resistant{
+    pub resisted: Vec<DamageType>,
+    pub immune: Vec<DamageType>,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Doubles damage of these types. Cancels out with a resistance to the same type
+pub struct Vulnerable{
+    pub damage_types: Vec<DamageType>,
+}
+
+#[derive(Component, Debug, Clone)]
+/// The last hit that landed, for kill credit and death messages. Not saved, deaths are settled the turn they happen
+pub struct LastDamagedBy{
+    pub source: Option<Entity>,
+    pub cause: String,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Hurts anyone who damages its owner from an adjacent tile. Armour passes it on to whoever wears it
+pub struct Thorns{
+    pub damage: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// A monster hurt by another creature goes after it instead of the player for a while
+pub struct Grudge{
+    pub target: Entity,
+    pub turns: i32,
 }
 
 #[derive(Component, Debug, ConvertSaveload, Clone)]
@@ -184,6 +272,172 @@ pub struct WantsToUnequipItem{
     pub item: Entity,
 }
 
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// A pile of coins lying on the floor, merged into the collector's wallet on pick up
+pub struct Gold{
+    pub amount: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Gold carried by an entity. Monsters drop theirs when they die
+pub struct Wallet{
+    pub gold: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Shopkeeper{ }
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToBuyItem{
+    pub item: Entity,
+    pub vendor: Entity,
+    pub price: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToSellItem{
+    pub item: Entity,
+    pub vendor: Entity,
+    pub price: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Name of the random table rolled for a drop when this entity dies
+pub struct LootTable{
+    pub table: String,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Not drawn or shown in tooltips until found
+pub struct Hidden{ }
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Uses this entity's effects on whatever moves onto its tile
+pub struct EntryTrigger{ }
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Added when an entity changes tile so entry triggers know to check it
+pub struct EntityMoved{ }
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Moves targets to a random open floor tile
+pub struct Teleports{ }
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Alerts every monster on the level to where the player is
+pub struct SoundsAlarm{ }
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Monster hunts the player for a few turns even without seeing them
+pub struct Alerted{
+    pub turns: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Unlocks a single locked door
+pub struct Key{ }
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Sets the targeted tiles alight for a number of turns
+pub struct StartsFire{
+    pub turns: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Fills the targeted tiles with poison gas of this density
+pub struct CreatesGas{
+    pub density: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// On fire, takes damage every turn until it burns out or reaches water
+pub struct Burning{
+    pub turns: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Carrying this back up to the town wins the game
+pub struct Artifact{ }
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Monster uses an ability on the player from range every few turns.
+/// The ability is a positionless entity built like an item so the item use system can resolve it
+pub struct UsesAbility{
+    pub ability: Entity,
+    pub range: i32,
+    pub cooldown: i32,
+    pub timer: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Monster calls in more monsters around itself every few turns
+pub struct SummonsMinions{
+    pub minion: String,
+    pub count: i32,
+    pub cooldown: i32,
+    pub timer: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Shatters when thrown, splashing its effect over the tiles around where it lands
+pub struct Potion{ }
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Wands and staves. Each use spends a charge, an empty one stays in the pack until it is recharged
+pub struct Charges{
+    pub current: i32,
+    pub max: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Regains a charge on its own every few turns
+pub struct RechargesOverTime{
+    pub turns: i32,
+    pub timer: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+/// Refills the charges of everything its user is carrying
+pub struct RechargesItems{ }
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Spent to cast spells, a point comes back every few turns
+pub struct Mana{
+    pub current: i32,
+    pub max: i32,
+    pub regen_turns: i32,
+    pub regen_timer: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// A learned spell. It carries the same effect components as items
+pub struct Spell{
+    pub mana_cost: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct KnownSpell{
+    pub owner: Entity,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+/// Spellbooks. Reading one teaches the named spell
+pub struct TeachesSpell{
+    pub spell: String,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToCastSpell{
+    pub spell: Entity,
+    pub target: Option<rltk::Point>,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToThrowItem{
+    pub item: Entity,
+    pub target: rltk::Point,
+}
+
 // serialization helpers
 
 pub struct SerializeMe;
@@ -191,4 +445,6 @@ pub struct SerializeMe;
 #[derive(Component, Clone, Serialize, Deserialize)]
 pub struct SerializationHelper{
     pub map: Map,
+    pub run_stats: RunStats,
+    pub visited_levels: VisitedLevels,
 }
\ No newline at end of file
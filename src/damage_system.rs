@@ -1,26 +1,191 @@
+use std::collections::VecDeque;
+
 use specs::prelude::*;
-use crate::{SuffersDamage, CombatStats, Player, GameLog, Name, RunState};
+use crate::{SuffersDamage, DamageInstance, DamageType, LastDamagedBy, Thorns, Grudge, CombatStats, Resistant, Vulnerable, Player, GameLog, LogEntry, Name, RunState, Position, Wallet, gold_pile, LootTable, Monster, InBackpack, Equipped, Map, loot_table, spawn_named_entity, corpse, UsesAbility, RunStats, write_morgue_file, record_high_score};
+
+/// How long a monster stays angry at whoever hurt it
+pub const GRUDGE_TURNS: i32 = 10;
+
+/// "a Goblin", "an Orc": how death messages name whoever did it
+pub fn with_article(name: &str) -> String{
+    let article = if name.starts_with(['A', 'E', 'I', 'O', 'U', 'a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+    format!("{} {}", article, name)
+}
 
 pub struct DamageSystem{ }
 
 impl<'a> System<'a> for DamageSystem{
     type SystemData = ( WriteStorage<'a, SuffersDamage>,
-                        WriteStorage<'a, CombatStats>);
+                        WriteStorage<'a, CombatStats>,
+                        Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, RunStats>,
+                        ReadStorage<'a, Resistant>,
+                        ReadStorage<'a, Vulnerable>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Position>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, GameLog>,
+                        // who did it
+                        ( WriteStorage<'a, LastDamagedBy>,
+                          ReadStorage<'a, Thorns>,
+                          ReadStorage<'a, Monster>,
+                          WriteStorage<'a, Grudge>,
+                        ),
+                    );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut suffers_damage, mut combat_stats) = data;
+        let (mut suffers_damage, mut combat_stats, entities, player_entity, mut run_stats,
+            resistant, vulnerable, equipped, names, positions, map, mut gamelog,
+            (mut last_damaged_by, thorns, monsters, mut grudges)) = data;
+
+        // every hit waiting to land, thorns add theirs to the back so they hurt within the same turn
+        let mut pending: VecDeque<(Entity, DamageInstance)> = (&mut suffers_damage, &entities).join()
+            .flat_map(|(damage, entity)| std::mem::take(&mut damage.instances).into_iter().map(move |instance| (entity, instance)))
+            .collect();
+        suffers_damage.clear();
+        let mut new_grudges: Vec<(Entity, Entity)> = Vec::new();
+
+        while let Some((entity, instance)) = pending.pop_front(){
+            if combat_stats.get(entity).is_none(){
+                continue;
+            }
+
+            // the creature's own traits plus whatever its armour gives it
+            let worn: Vec<Entity> = (&entities, &equipped).join()
+                .filter(|(_item, equip)| equip.owner == entity)
+                .map(|(item, _equip)| item)
+                .chain(std::iter::once(entity))
+                .collect();
+            let has_trait = |check: &dyn Fn(Entity) -> bool| worn.iter().any(|e| check(*e));
 
-        for (suffers_damage, combat_stats) in (&suffers_damage, &mut combat_stats).join(){
-            combat_stats.hp -= suffers_damage.amount.iter().sum::<i32>();
+            // only mention it when the player is hit or can see it happen
+            let seen = entity == *player_entity || positions.get(entity)
+                .is_some_and(|pos| map.currently_visible_tiles[map.xy_idx(pos.x, pos.y)]);
+            let name = names.get(entity).map(|n| n.name.clone()).unwrap_or_default();
+            let (subject, object) = if entity == *player_entity { ("You".to_string(), "you".to_string()) } else { (name.clone(), name.clone()) };
+
+            let (amount, damage_type) = (instance.amount, instance.damage_type);
+            let immune = has_trait(&|e| resistant.get(e).is_some_and(|r| r.immune.contains(&damage_type)));
+            let resists = has_trait(&|e| resistant.get(e).is_some_and(|r| r.resisted.contains(&damage_type)));
+            let weak = has_trait(&|e| vulnerable.get(e).is_some_and(|v| v.damage_types.contains(&damage_type)));
+
+            // a resistance and a vulnerability to the same type cancel out
+            let (taken, note) = match (immune, resists, weak){
+                (true, _, _) => (0, Some(LogEntry::new().text(format!("The {} damage has no effect on ", damage_type.name())).name(&object).text(", immune!"))),
+                (false, true, false) => (amount / 2, Some(LogEntry::new().name(&subject).text(" resisted ").damage(amount - amount / 2).text(format!(" {} damage.", damage_type.name())))),
+                (false, false, true) => (amount * 2, Some(LogEntry::new().name(&subject).text(" took ").damage(amount).text(format!(" extra {} damage, ", damage_type.name())).warning("vulnerable!"))),
+                _ => (amount, None),
+            };
+
+            if seen || instance.source == Some(*player_entity){
+                if let Some(hit) = hit_entry(&instance, taken, entity, *player_entity, &names, &thorns){
+                    gamelog.add(hit);
+                }
+            }
+            if let (Some(note), true) = (note, seen){
+                gamelog.add(note);
+            }
+
+            if taken > 0{
+                if let Some(stats) = combat_stats.get_mut(entity){
+                    stats.hp -= taken;
+                }
+                if entity == *player_entity{
+                    run_stats.damage_taken += taken;
+                }
+                last_damaged_by.insert(entity, LastDamagedBy{ source: instance.source, cause: instance.cause.clone() })
+                    .expect("Could not record who dealt damage.");
+                if instance.source == Some(*player_entity){
+                    run_stats.damage_dealt += taken;
+                }
+            }
+
+            let attacker = match instance.source{
+                Some(attacker) if attacker != entity && combat_stats.get(attacker).is_some() => attacker,
+                _ => continue,
+            };
+
+            // thorns only prick whoever is close enough to touch, when the hit got through, and never answer other thorns
+            let adjacent = match (positions.get(attacker), positions.get(entity)){
+                (Some(a), Some(b)) => (a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1,
+                _ => false,
+            };
+            let from_thorns = instance.item.is_some_and(|item| thorns.get(item).is_some());
+            if let (Some(thorny), true, false, true) = (worn.iter().find(|e| thorns.get(**e).is_some()), adjacent, from_thorns, taken > 0){
+                let thorny_name = names.get(*thorny).map(|n| n.name.clone()).unwrap_or_default();
+                pending.push_back((attacker, DamageInstance{
+                    amount: thorns.get(*thorny).unwrap().damage,
+                    damage_type: DamageType::Physical,
+                    source: Some(entity),
+                    item: Some(*thorny),
+                    cause: format!("the thorns of {}", thorny_name),
+                    melee: false,
+                }));
+            }
+
+            // monsters hurt by anyone but the player turn on them
+            if monsters.get(entity).is_some() && attacker != *player_entity{
+                new_grudges.push((entity, attacker));
+            }
         }
 
-        // clear suffers_damage from entites, it has been calculated and applied already
-        suffers_damage.clear();
+        for (monster, target) in new_grudges{
+            let already = grudges.get(monster).is_some_and(|grudge| grudge.target == target);
+            let seen = positions.get(monster).is_some_and(|pos| map.currently_visible_tiles[map.xy_idx(pos.x, pos.y)]);
+            if !already && seen{
+                if let (Some(monster_name), Some(target_name)) = (names.get(monster), names.get(target)){
+                    gamelog.add(LogEntry::new().name(&monster_name.name).warning(" turns on ").name(&target_name.name).warning("!"));
+                }
+            }
+            grudges.insert(monster, Grudge{ target, turns: GRUDGE_TURNS })
+                .expect("Could not add grudge.");
+        }
+    }
+}
+
+/// How a hit reads in the log, with the damage left after resistances. Hazards describe themselves
+fn hit_entry(instance: &DamageInstance, taken: i32, target: Entity, player: Entity, names: &ReadStorage<Name>, thorns: &ReadStorage<Thorns>) -> Option<LogEntry>{
+    if taken <= 0{
+        return None;
+    }
+
+    let name = |entity: Entity| names.get(entity).map(|n| n.name.clone()).unwrap_or_default();
+    let by_player = instance.source == Some(player);
+    let target_name = match (target == player, by_player){
+        (true, true) => "yourself".to_string(),
+        (true, false) => "you".to_string(),
+        (false, _) => name(target),
+    };
+    let kind = instance.damage_type.name();
+
+    match (instance.item, instance.source){
+        (_, Some(attacker)) if instance.melee => {
+            let how = if instance.damage_type == DamageType::Physical { " hp!".to_string() } else { format!(" {} damage!", kind) };
+            Some(LogEntry::new().name(name(attacker)).text(" hits ").name(name(target)).text(" for ").damage(taken).text(how))
+        },
+        (Some(item), _) if thorns.get(item).is_some() =>
+            Some(LogEntry::new().text("The thorns of ").name(name(item)).text(" prick ").name(target_name).text(" for ").damage(taken).text(" hp!")),
+        (Some(item), _) if by_player =>
+            Some(LogEntry::new().text("You hit ").name(target_name).text(" for ").damage(taken).text(format!(" {} damage with ", kind)).name(name(item)).text(".")),
+        (Some(dealt_by), _) | (None, Some(dealt_by)) =>
+            Some(LogEntry::new().name(name(dealt_by)).text(" hits ").name(target_name).text(" for ").damage(taken).text(format!(" {} damage.", kind))),
+        (None, None) => None,
     }
 }
 
 pub fn delete_dead_entities(world: &mut World){
     let mut dead_entities: Vec<Entity> = Vec::new();
+    // (x, y, amount) of gold the dead leave behind
+    let mut gold_drops: Vec<(i32, i32, i32)> = Vec::new();
+    // (entity, x, y) of the dead whose belongings fall to the floor
+    let mut death_tiles: Vec<(Entity, i32, i32)> = Vec::new();
+    // (x, y, loot table name) to roll for each dead entity with a loot table
+    let mut loot_drops: Vec<(i32, i32, String)> = Vec::new();
+    // (x, y, name) of monsters that leave a corpse
+    let mut corpses: Vec<(i32, i32, String)> = Vec::new();
+    let mut player_died = false;
 
     // scope to appease borrow checker
     // world is borrowed immutably when getting combat stats
@@ -34,18 +199,66 @@ pub fn delete_dead_entities(world: &mut World){
         let players = world.read_storage::<Player>();
         let mut gamelog = world.write_resource::<GameLog>();
         let names = world.read_storage::<Name>();
+        let positions = world.read_storage::<Position>();
+        let wallets = world.read_storage::<Wallet>();
+        let loot_tables = world.read_storage::<LootTable>();
+        let monsters = world.read_storage::<Monster>();
+        let abilities = world.read_storage::<UsesAbility>();
+        let mut run_stats = world.write_resource::<RunStats>();
+        let last_damaged_by = world.read_storage::<LastDamagedBy>();
+        let player_entity = world.fetch::<Entity>();
+        let depth = world.fetch::<Map>().depth;
 
         for (combat_stats, entity) in (&combat_stats, &entities).join(){
             if combat_stats.hp <= 0 {
                 match players.get(entity){
                     Some(_) => {
+                        // the dead player stays around for the game over screen, only end the run once
                         let mut run_state = world.write_resource::<RunState>();
-                        *run_state = RunState::GameOver;
+                        if *run_state != RunState::GameOver{
+                            *run_state = RunState::GameOver;
+                            let cause = last_damaged_by.get(entity).map(|last| last.cause.clone()).unwrap_or_else(|| "something".to_string());
+                            run_stats.cause_of_death = Some(format!("{} on depth {}", cause, depth));
+                            player_died = true;
+                        }
                     },
                     None => {
                         let entity_name = names.get(entity);
+                        let last = last_damaged_by.get(entity);
+                        let by_player = last.is_some_and(|last| last.source == Some(*player_entity));
+                        // name the killer when another creature did it, the player already knows their own kills
+                        let killer = last.filter(|last| !by_player && last.source.is_some_and(|source| source != entity));
                         if let Some(name) = entity_name{
-                            gamelog.entries.push(format!("{} is dead.", name.name));
+                            match killer{
+                                Some(killer) => gamelog.add(LogEntry::new().name(&name.name).colour(rltk::RGB::named(rltk::RED), " was killed by ").text(format!("{}.", killer.cause))),
+                                None => gamelog.add(LogEntry::new().name(&name.name).colour(rltk::RGB::named(rltk::RED), " is dead.")),
+                            }
+                        }
+
+                        if let Some(pos) = positions.get(entity){
+                            death_tiles.push((entity, pos.x, pos.y));
+
+                            if let Some(wallet) = wallets.get(entity){
+                                if wallet.gold > 0{
+                                    gold_drops.push((pos.x, pos.y, wallet.gold));
+                                }
+                            }
+
+                            if let Some(loot) = loot_tables.get(entity){
+                                loot_drops.push((pos.x, pos.y, loot.table.clone()));
+                            }
+
+                            if let (Some(_monster), Some(name)) = (monsters.get(entity), entity_name){
+                                corpses.push((pos.x, pos.y, name.name.clone()));
+                                if by_player{
+                                    run_stats.add_kill(&name.name);
+                                }
+                            }
+                        }
+
+                        // abilities have no position of their own and die with their user
+                        if let Some(ability) = abilities.get(entity){
+                            dead_entities.push(ability.ability);
                         }
 
                         dead_entities.push(entity);
@@ -56,8 +269,61 @@ pub fn delete_dead_entities(world: &mut World){
         }
     }
 
+    if player_died{
+        record_high_score(world, false);
+        // a failed write should not take the game over screen down with it
+        match write_morgue_file(world){
+            Ok(morgue_file) => world.write_resource::<GameLog>().log(format!("Your run was recorded in {}", morgue_file)),
+            Err(error) => world.write_resource::<GameLog>().log(format!("Could not record your run: {}", error)),
+        }
+    }
+
+    // anything carried or equipped by the dead falls onto the tile they died on
+    {
+        let entities = world.entities();
+        let mut backpacks = world.write_storage::<InBackpack>();
+        let mut equipped = world.write_storage::<Equipped>();
+        let mut positions = world.write_storage::<Position>();
+
+        for (dead, x, y) in death_tiles.iter(){
+            let mut dropped_items: Vec<Entity> = Vec::new();
+
+            for (item, backpack) in (&entities, &backpacks).join(){
+                if backpack.owner == *dead{
+                    dropped_items.push(item);
+                }
+            }
+            for (item, equip) in (&entities, &equipped).join(){
+                if equip.owner == *dead{
+                    dropped_items.push(item);
+                }
+            }
+
+            for item in dropped_items{
+                backpacks.remove(item);
+                equipped.remove(item);
+                positions.insert(item, Position { x: *x, y: *y })
+                    .expect("Could not drop item of dead entity.");
+            }
+        }
+    }
+
     for dead in dead_entities{
         world.delete_entity(dead)
             .expect("Unable to delete entity.");
     }
+
+    for (x, y, amount) in gold_drops{
+        gold_pile(world, x, y, amount);
+    }
+
+    let depth = world.fetch::<Map>().depth;
+    for (x, y, table) in loot_drops{
+        let roll = loot_table(&table, depth).roll(&mut world.write_resource::<rltk::RandomNumberGenerator>());
+        spawn_named_entity(world, &roll, x, y, depth);
+    }
+
+    for (x, y, name) in corpses{
+        corpse(world, x, y, &name);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,379 @@
+use std::collections::VecDeque;
+
+use specs::prelude::*;
+use crate::{GameLog, LogEntry, Name, Map, Position, CombatStats, SuffersDamage, IsConfused, HasMovementSpeedModifier, FOV, TileType, Monster, Alerted,
+    InBackpack, RunStats, ProvidesHealing, InflictsDamage, CausesConfusion, GivesMovementSpeed, Teleports, SoundsAlarm, StartsFire, CreatesGas,
+    RechargesItems, TeachesSpell, DamageType, DamageInstance, with_article, KnownSpell, Charges, Consumable, Spell, Potion, AreaOfEffect, ignite, spawner};
+
+/// What an effect does once it lands
+#[derive(Clone, Debug)]
+pub enum EffectType{
+    /// Every effect an item, spell or trap carries, resolved together so it is only used up once
+    ItemUse{ item: Entity },
+    Damage{ amount: i32, damage_type: DamageType },
+    Healing{ amount: i32 },
+    Confusion{ turns: i32 },
+    MovementSpeed{ speed_modifier: i32, turns: i32 },
+    Teleport,
+    Alarm,
+    Fire{ turns: i32 },
+    Gas{ density: i32 },
+    Recharge,
+    LearnSpell{ spell: String },
+}
+
+/// Where an effect lands
+#[derive(Clone, Debug)]
+pub enum Targets{
+    Entity{ target: Entity },
+    Tile{ idx: usize },
+    Area{ tiles: Vec<usize> },
+}
+
+#[derive(Clone, Debug)]
+pub struct Effect{
+    /// Who is responsible: the creature using something, or the trap that went off
+    pub source: Option<Entity>,
+    /// The item, spell or trap it came out of
+    pub item: Option<Entity>,
+    pub effect: EffectType,
+    pub targets: Targets,
+}
+
+/// Effects waiting to be resolved, filled by the systems and emptied by run_effects_queue
+#[derive(Default)]
+pub struct EffectQueue{
+    queue: VecDeque<Effect>,
+}
+
+impl EffectQueue{
+    pub fn add(&mut self, source: Option<Entity>, item: Option<Entity>, effect: EffectType, targets: Targets){
+        self.queue.push_back(Effect{ source, item, effect, targets });
+    }
+}
+
+/// The tile aimed at, or everything in view of it for area effects
+pub fn aimed_targets(map: &Map, target: rltk::Point, area_of_effect: Option<&AreaOfEffect>) -> Targets{
+    match area_of_effect{
+        None => Targets::Tile{ idx: map.xy_idx(target.x, target.y) },
+        Some(aoe) => Targets::Area{
+            tiles: rltk::field_of_view(target, aoe.radius, map).iter()
+                .filter(|p| map.is_idx_valid(p.x, p.y))
+                .map(|p| map.xy_idx(p.x, p.y))
+                .collect(),
+        },
+    }
+}
+
+/// Resolve everything queued, including whatever the effects themselves queue
+pub fn run_effects_queue(world: &mut World){
+    loop{
+        let effect = world.write_resource::<EffectQueue>().queue.pop_front();
+        match effect{
+            Some(effect) => resolve_effect(world, &effect),
+            None => break,
+        }
+    }
+}
+
+fn resolve_effect(world: &mut World, effect: &Effect){
+    match &effect.effect{
+        EffectType::ItemUse{ item } => use_item(world, effect.source, *item, &effect.targets),
+        EffectType::Alarm => sound_alarm(world),
+        EffectType::Fire{ .. } | EffectType::Gas{ .. } => {
+            for idx in target_tiles(&effect.targets){
+                affect_tile(world, effect, idx);
+            }
+        },
+        _ => {
+            for target in target_entities(world, &effect.targets){
+                affect_entity(world, effect, target);
+            }
+        },
+    }
+}
+
+/// Tiles an effect lands on. Effects aimed at an entity don't touch the map
+fn target_tiles(targets: &Targets) -> Vec<usize>{
+    match targets{
+        Targets::Entity{ .. } => Vec::new(),
+        Targets::Tile{ idx } => vec![*idx],
+        Targets::Area{ tiles } => tiles.clone(),
+    }
+}
+
+fn target_entities(world: &World, targets: &Targets) -> Vec<Entity>{
+    let map = world.fetch::<Map>();
+    match targets{
+        Targets::Entity{ target } => vec![*target],
+        Targets::Tile{ idx } => map.tile_content[*idx].clone(),
+        Targets::Area{ tiles } => tiles.iter().flat_map(|idx| map.tile_content[*idx].iter().copied()).collect(),
+    }
+}
+
+/// Turn an item's effect components into effects on its targets, then spend a charge or use it up
+fn use_item(world: &mut World, source: Option<Entity>, item: Entity, targets: &Targets){
+    let player_entity = *world.fetch::<Entity>();
+    let by_player = source == Some(player_entity);
+    let item_name = name_of(world, item);
+
+    // an empty wand does nothing until it is recharged
+    if world.read_storage::<Charges>().get(item).is_some_and(|charges| charges.current <= 0){
+        if by_player{
+            world.fetch_mut::<GameLog>().add(LogEntry::new().text("The ").name(&item_name).text(" has no charges left."));
+        }
+        return;
+    }
+
+    let mut effects: Vec<EffectType> = Vec::new();
+    if let Some(fire) = world.read_storage::<StartsFire>().get(item){
+        effects.push(EffectType::Fire{ turns: fire.turns });
+    }
+    if let Some(gas) = world.read_storage::<CreatesGas>().get(item){
+        effects.push(EffectType::Gas{ density: gas.density });
+    }
+    if let Some(confusion) = world.read_storage::<CausesConfusion>().get(item){
+        effects.push(EffectType::Confusion{ turns: confusion.turns });
+    }
+    if let Some(speed) = world.read_storage::<GivesMovementSpeed>().get(item){
+        effects.push(EffectType::MovementSpeed{ speed_modifier: speed.speed_modifier, turns: speed.turns });
+    }
+    if world.read_storage::<Teleports>().get(item).is_some(){
+        effects.push(EffectType::Teleport);
+    }
+    if world.read_storage::<SoundsAlarm>().get(item).is_some(){
+        effects.push(EffectType::Alarm);
+    }
+    if let Some(healing) = world.read_storage::<ProvidesHealing>().get(item){
+        effects.push(EffectType::Healing{ amount: healing.heal_amount });
+    }
+    if let Some(damage) = world.read_storage::<InflictsDamage>().get(item){
+        effects.push(EffectType::Damage{ amount: damage.damage, damage_type: damage.damage_type });
+    }
+    if world.read_storage::<RechargesItems>().get(item).is_some(){
+        effects.push(EffectType::Recharge);
+    }
+    if let Some(teaches) = world.read_storage::<TeachesSpell>().get(item){
+        // the book is kept if its reader knows the spell already
+        let reader = source.unwrap_or(player_entity);
+        let already_known = (&world.read_storage::<KnownSpell>(), &world.read_storage::<Name>()).join()
+            .any(|(known, name)| known.owner == reader && name.name == teaches.spell);
+        if already_known{
+            if reader == player_entity{
+                world.fetch_mut::<GameLog>().add(LogEntry::new().text("You already know ").name(&teaches.spell).text("."));
+            }
+        }else{
+            effects.push(EffectType::LearnSpell{ spell: teaches.spell.clone() });
+        }
+    }
+
+    if effects.is_empty(){
+        return;
+    }
+
+    // resolved right away, the item may be gone by the time anything queued after it comes up
+    for effect in effects{
+        resolve_effect(world, &Effect{ source, item: Some(item), effect, targets: targets.clone() });
+    }
+
+    if by_player && world.read_storage::<Spell>().get(item).is_none(){
+        world.write_resource::<RunStats>().items_used += 1;
+    }
+
+    // charged items are never used up, only emptied
+    let mut emptied = false;
+    if let Some(charges) = world.write_storage::<Charges>().get_mut(item){
+        charges.current -= 1;
+        emptied = charges.current <= 0;
+    }
+    if emptied && by_player{
+        world.fetch_mut::<GameLog>().add(LogEntry::new().text("The ").name(&item_name).text(" is now empty."));
+    }
+
+    let mut used_up = false;
+    if let Some(consumable) = world.write_storage::<Consumable>().get_mut(item){
+        consumable.charges -= 1;
+        used_up = consumable.charges <= 0;
+    }
+    if used_up{
+        world.delete_entity(item)
+            .expect("Could not delete consumable entity.");
+    }
+}
+
+fn affect_tile(world: &mut World, effect: &Effect, idx: usize){
+    let mut map = world.write_resource::<Map>();
+    match effect.effect{
+        EffectType::Fire{ turns } => ignite(&mut map, idx, turns),
+        EffectType::Gas{ density } if map.can_hold_gas(idx) => map.gas[idx] = i32::max(map.gas[idx], density),
+        _ => {},
+    }
+}
+
+fn affect_entity(world: &mut World, effect: &Effect, target: Entity){
+    // most effects only work on creatures, not the items and traps sharing their tile
+    let is_creature = world.read_storage::<CombatStats>().get(target).is_some();
+
+    match &effect.effect{
+        EffectType::Damage{ amount, damage_type } if is_creature => inflict_damage(world, effect, target, *amount, *damage_type),
+        EffectType::Healing{ amount } if is_creature => heal(world, effect, target, *amount),
+        EffectType::Confusion{ turns } if is_creature => {
+            world.write_storage::<IsConfused>().insert(target, IsConfused{ turns: *turns })
+                .expect("Could not add is confused component to target.");
+            log_effect(world, effect, target, |you, item, target_name| if you {
+                LogEntry::new().text("You use ").name(item).text(" on ").name(target_name).text(format!(" confusing them for {} turns", turns))
+            } else {
+                LogEntry::new().name(item).text(" confuses ").name(target_name).text(format!(" for {} turns", turns))
+            });
+        },
+        EffectType::MovementSpeed{ speed_modifier, turns } if is_creature => {
+            world.write_storage::<HasMovementSpeedModifier>().insert(target, HasMovementSpeedModifier{ speed_modifier: *speed_modifier, max_turns: *turns, turns_used: 0 })
+                .expect("Could not add movement speed modifier.");
+            log_effect(world, effect, target, |you, item, target_name| if you {
+                LogEntry::new().text("You use ").name(item).text(format!(" to gain a movement speed of x{} for {} turns", speed_modifier, turns))
+            } else {
+                LogEntry::new().name(item).text(" gives ").name(target_name).text(format!(" a movement speed of x{} for {} turns", speed_modifier, turns))
+            });
+        },
+        EffectType::Teleport if is_creature => teleport(world, target),
+        EffectType::Recharge => recharge(world, target),
+        EffectType::LearnSpell{ spell } => {
+            spawner::learn_spell(world, spell, target);
+            if target == *world.fetch::<Entity>(){
+                world.fetch_mut::<GameLog>().add(LogEntry::new().text("You learn ").name(spell).text("!"));
+            }
+        },
+        _ => {},
+    }
+}
+
+fn inflict_damage(world: &mut World, effect: &Effect, target: Entity, amount: i32, damage_type: DamageType){
+    let player_entity = *world.fetch::<Entity>();
+
+    // hurting yourself is blamed on the item, anything else on whoever used it
+    let cause = match (effect.source, effect.item){
+        (Some(source), _) if source != target => with_article(&name_of(world, source)),
+        (_, Some(item)) if target == player_entity => format!("your own {}", name_of(world, item)),
+        (_, Some(item)) => format!("its own {}", name_of(world, item)),
+        (_, None) => "something".to_string(),
+    };
+    SuffersDamage::new_damage(&mut world.write_storage::<SuffersDamage>(), target, DamageInstance{
+        amount,
+        damage_type,
+        source: effect.source,
+        item: effect.item,
+        cause,
+        melee: false,
+    });
+}
+
+fn heal(world: &mut World, effect: &Effect, target: Entity, amount: i32){
+    if let Some(stats) = world.write_storage::<CombatStats>().get_mut(target){
+        stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+    }
+
+    let drunk = effect.source == Some(target) && effect.item.is_some_and(|item| world.read_storage::<Potion>().get(item).is_some());
+    let on_player = target == *world.fetch::<Entity>();
+    log_effect(world, effect, target, |you, item, target_name| if you && drunk {
+        LogEntry::new().text("You drink the ").name(item).text(" for ").healing(amount).text(" hp")
+    } else if on_player {
+        LogEntry::new().name(item).text(" heals you for ").healing(amount).text(" hp")
+    } else {
+        LogEntry::new().name(item).text(" heals ").name(target_name).text(" for ").healing(amount).text(" hp")
+    });
+}
+
+fn teleport(world: &mut World, target: Entity){
+    let player_entity = *world.fetch::<Entity>();
+    let map = world.fetch::<Map>();
+    let mut rng = world.write_resource::<rltk::RandomNumberGenerator>();
+
+    // pick from the free floor there is, a packed level leaves the target where it stands
+    let free_floor: Vec<usize> = (0..map.tiles.len())
+        .filter(|idx| map.tiles[*idx] == TileType::Floor && !map.blocked_tiles[*idx])
+        .collect();
+    if free_floor.is_empty(){
+        if target == player_entity{
+            world.fetch_mut::<GameLog>().log("You flicker for a moment, but there is nowhere to go.");
+        }
+        return;
+    }
+    let destination_idx = free_floor[rng.range(0, free_floor.len())];
+
+    if let Some(pos) = world.write_storage::<Position>().get_mut(target){
+        pos.x = destination_idx as i32 % map.width;
+        pos.y = destination_idx as i32 / map.width;
+
+        if target == player_entity{
+            let mut player_position = world.write_resource::<rltk::Point>();
+            player_position.x = pos.x;
+            player_position.y = pos.y;
+            world.fetch_mut::<GameLog>().log("You are teleported away!");
+        }
+    }
+
+    if let Some(target_fov) = world.write_storage::<FOV>().get_mut(target){
+        target_fov.needs_update = true;
+    }
+}
+
+fn sound_alarm(world: &mut World){
+    let entities = world.entities();
+    let monsters = world.read_storage::<Monster>();
+    let positions = world.read_storage::<Position>();
+    let mut alerted = world.write_storage::<Alerted>();
+
+    // only this level hears it, monsters on levels the player left have no position here
+    for (monster, _m, _pos) in (&entities, &monsters, &positions).join(){
+        alerted.insert(monster, Alerted { turns: 10 })
+            .expect("Could not alert monster.");
+    }
+    world.fetch_mut::<GameLog>().add(LogEntry::new().warning("A loud alarm rings through the level!"));
+}
+
+/// Refill the charges of everything the target carries
+fn recharge(world: &mut World, target: Entity){
+    let mut recharged = 0;
+    for (charges, pack) in (&mut world.write_storage::<Charges>(), &world.read_storage::<InBackpack>()).join(){
+        if pack.owner == target && charges.current < charges.max{
+            charges.current = charges.max;
+            recharged += 1;
+        }
+    }
+
+    if target == *world.fetch::<Entity>(){
+        if recharged > 0{
+            world.fetch_mut::<GameLog>().add(LogEntry::new().text("Your wands and staves crackle with fresh power."));
+        }else{
+            world.fetch_mut::<GameLog>().add(LogEntry::new().text("You feel a surge of power, but nothing happens."));
+        }
+    }
+}
+
+fn name_of(world: &World, entity: Entity) -> String{
+    world.read_storage::<Name>().get(entity).map(|name| name.name.clone()).unwrap_or_default()
+}
+
+/// Log a line when the player is involved or can see the target. The line is built from whether the player
+/// caused it, the item's name and the target's name ("you" or "yourself" for the player)
+fn log_effect<F>(world: &World, effect: &Effect, target: Entity, line: F)
+    where F: FnOnce(bool, &str, &str) -> LogEntry{
+    let player_entity = *world.fetch::<Entity>();
+    let by_player = effect.source == Some(player_entity);
+
+    let seen = world.read_storage::<Position>().get(target).is_some_and(|pos| {
+        let map = world.fetch::<Map>();
+        map.is_idx_valid(pos.x, pos.y) && map.currently_visible_tiles[map.xy_idx(pos.x, pos.y)]
+    });
+    if !by_player && target != player_entity && !seen{
+        return;
+    }
+
+    let item_name = effect.item.or(effect.source).map(|item| name_of(world, item)).unwrap_or_default();
+    let target_name = match (target == player_entity, by_player){
+        (true, true) => "yourself".to_string(),
+        (true, false) => "you".to_string(),
+        (false, _) => name_of(world, target),
+    };
+    world.fetch_mut::<GameLog>().add(line(by_player, &item_name, &target_name));
+}
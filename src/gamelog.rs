@@ -1,3 +1,100 @@
+use std::collections::VecDeque;
+
+use rltk::RGB;
+
+/// Oldest entries are dropped once the log holds this many
+pub const MAX_LOG_ENTRIES: usize = 200;
+
+/// A piece of a log line drawn in its own colour
+#[derive(Clone, PartialEq)]
+pub struct LogFragment{
+    pub colour: RGB,
+    pub text: String,
+}
+
+/// One line of the log, built up fragment by fragment
+#[derive(Clone)]
+pub struct LogEntry{
+    pub fragments: Vec<LogFragment>,
+    /// turn the entry was last logged on
+    pub turn: i32,
+    /// how many times in a row the same line was logged
+    pub count: i32,
+}
+
+impl Default for LogEntry{
+    fn default() -> Self {
+        LogEntry::new()
+    }
+}
+
+impl LogEntry{
+    pub fn new() -> LogEntry{
+        LogEntry{ fragments: Vec::new(), turn: 0, count: 1 }
+    }
+
+    pub fn colour<S: ToString>(mut self, colour: RGB, text: S) -> LogEntry{
+        self.fragments.push(LogFragment{ colour, text: text.to_string() });
+        self
+    }
+
+    /// Plain white text
+    pub fn text<S: ToString>(self, text: S) -> LogEntry{
+        self.colour(RGB::named(rltk::WHITE), text)
+    }
+
+    /// Names of creatures and items
+    pub fn name<S: ToString>(self, name: S) -> LogEntry{
+        self.colour(RGB::named(rltk::YELLOW), name)
+    }
+
+    pub fn damage(self, amount: i32) -> LogEntry{
+        self.colour(RGB::named(rltk::RED), amount)
+    }
+
+    pub fn healing(self, amount: i32) -> LogEntry{
+        self.colour(RGB::named(rltk::GREEN), amount)
+    }
+
+    /// Something the player should not miss
+    pub fn warning<S: ToString>(self, text: S) -> LogEntry{
+        self.colour(RGB::named(rltk::ORANGE), text)
+    }
+
+    /// The whole line without colours
+    pub fn plain_text(&self) -> String{
+        self.fragments.iter().map(|fragment| fragment.text.as_str()).collect()
+    }
+}
+
+#[derive(Default)]
 pub struct GameLog{
-    pub entries: Vec<String>,
-}
\ No newline at end of file
+    pub entries: VecDeque<LogEntry>,
+    /// stamped onto new entries
+    pub turn: i32,
+}
+
+impl GameLog{
+    /// Add a line, repeats of the last line bump its count instead of taking up another line
+    pub fn add(&mut self, mut entry: LogEntry){
+        entry.turn = self.turn;
+
+        if let Some(last) = self.entries.back_mut(){
+            if last.fragments == entry.fragments{
+                last.count += 1;
+                last.turn = entry.turn;
+                return;
+            }
+        }
+
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_LOG_ENTRIES{
+            self.entries.pop_front();
+        }
+    }
+
+    /// Add a plain white line
+    pub fn log<S: ToString>(&mut self, text: S){
+        self.add(LogEntry::new().text(text));
+    }
+}
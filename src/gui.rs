@@ -1,12 +1,13 @@
 use specs::{World, WorldExt, Join, Entity};
 use rltk::{Rltk, RGB, VirtualKeyCode, Point};
 
-use crate::{CombatStats, Player, GameLog, Name, Position, Map, InBackpack, FOV, Consumable, RunState, does_save_exist, Equipped};
+use crate::{CombatStats, Player, GameLog, Name, Position, Map, InBackpack, FOV, Consumable, RunState, does_save_exist, Equipped, Wallet, item_price, item_sell_price, Hidden, screen_to_map, map_to_screen, VIEWPORT_WIDTH, VIEWPORT_HEIGHT, RunStats, HighScoreTable, LogEntry, MeleePowerBonus, DefenseBonus, ProvidesHealing, InflictsDamage, AreaOfEffect, Ranged, CausesConfusion, Equippable, IsConfused, Burning, Alerted, HasMovementSpeedModifier, Monster, Item, EntryTrigger, Keymap, Action, KEYMAP_FILE_PATH, AutoMove, travel_route, Charges, RechargesOverTime, Mana, Spell, TeachesSpell, known_spells, MeleeDamageType, Resistant, Vulnerable, DamageType, Thorns, Grudge, Artifact};
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum MainMenuSelection{
     NewGame,
     LoadGame,
+    HighScores,
     Quit,
 }
 
@@ -32,7 +33,19 @@ pub fn draw_ui(world: &World, context: &mut Rltk){
         let player_hp = format!("HP: {} / {}", combat_stats.hp, combat_stats.max_hp);
 
         context.print_color(12, 43, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &player_hp);
-        context.draw_bar_horizontal(28, 43, 51, combat_stats.hp, combat_stats.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+        context.draw_bar_horizontal(38, 43, 41, combat_stats.hp, combat_stats.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    }
+
+    // mana sits on the bottom edge of the box
+    let mana = world.read_storage::<Mana>();
+    if let Some(mana) = mana.get(*world.fetch::<Entity>()){
+        context.print_color(12, 49, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), format!("MP: {} / {}", mana.current, mana.max));
+        context.draw_bar_horizontal(26, 49, 20, mana.current, mana.max, RGB::named(rltk::BLUE), RGB::named(rltk::BLACK));
+    }
+
+    let wallets = world.read_storage::<Wallet>();
+    if let Some(wallet) = wallets.get(*world.fetch::<Entity>()){
+        context.print_color(26, 43, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), format!("Gold: {}", wallet.gold));
     }
 
     // show game log
@@ -40,12 +53,14 @@ pub fn draw_ui(world: &World, context: &mut Rltk){
     
     let starting_y = 44;
     let max_y = 49;
-    for (i, log) in gamelogs.entries.iter().rev().enumerate(){
+    for (i, entry) in gamelogs.entries.iter().rev().enumerate(){
         if (starting_y + i) < max_y{
-            context.print(2, starting_y+i, log);
+            draw_log_entry(context, 2, (starting_y + i) as i32, entry);
         }
     }
 
+    draw_travel_route(world, context);
+
     // draw mouse
     let mouse_position = context.mouse_pos();
     context.set_bg(mouse_position.0, mouse_position.1, RGB::named(rltk::MAGENTA));
@@ -54,20 +69,108 @@ pub fn draw_ui(world: &World, context: &mut Rltk){
 
 }
 
+/// Shade the route a click would walk, while the player is free to click
+fn draw_travel_route(world: &World, context: &mut Rltk){
+    if *world.fetch::<RunState>() != RunState::AwaitingInput || world.fetch::<AutoMove>().mode.is_some(){
+        return;
+    }
+
+    let mouse_position = context.mouse_pos();
+    if mouse_position.0 >= VIEWPORT_WIDTH || mouse_position.1 >= VIEWPORT_HEIGHT{
+        return;
+    }
+
+    let destination = screen_to_map(world, Point::new(mouse_position.0, mouse_position.1));
+    for step in travel_route(world, destination){
+        if let Some(screen_point) = map_to_screen(world, step){
+            context.set_bg(screen_point.x, screen_point.y, RGB::named(rltk::DARKSLATEGRAY));
+        }
+    }
+}
+
+/// Highlight a menu row while the mouse is over it. True if the mouse is on it
+fn menu_row_hovered(context: &mut Rltk, x1: i32, x2: i32, y: i32) -> bool{
+    let (mouse_x, mouse_y) = context.mouse_pos();
+    if mouse_y != y || mouse_x < x1 || mouse_x > x2{
+        return false;
+    }
+
+    for x in x1..=x2{
+        context.set_bg(x, y, RGB::named(rltk::DARKSLATEGRAY));
+    }
+    true
+}
+
+/// Turn stamp, then each fragment in its colour, then how often it repeated
+fn draw_log_entry(context: &mut Rltk, x: i32, y: i32, entry: &LogEntry){
+    let stamp = format!("[{}] ", entry.turn);
+    context.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), &stamp);
+
+    let mut fragment_x = x + stamp.len() as i32;
+    for fragment in entry.fragments.iter(){
+        context.print_color(fragment_x, y, fragment.colour, RGB::named(rltk::BLACK), &fragment.text);
+        fragment_x += fragment.text.len() as i32;
+    }
+
+    if entry.count > 1{
+        context.print_color(fragment_x, y, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), format!(" x{}", entry.count));
+    }
+}
+
+/// Full screen scrollback of the whole log, newest at the bottom.
+/// Returns the scroll offset to show next frame, counted in lines up from the newest entry
+pub fn show_log(world: &World, context: &mut Rltk, scroll: i32) -> (ItemMenuResult, i32){
+    const PAGE_HEIGHT: i32 = 44;
+
+    let gamelog = world.fetch::<GameLog>();
+    let keymap = world.fetch::<Keymap>();
+    let max_scroll = i32::max(0, gamelog.entries.len() as i32 - PAGE_HEIGHT);
+    let scroll = scroll.clamp(0, max_scroll);
+
+    context.draw_box(0, 0, 79, 49, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color(3, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Message Log");
+    context.print_color(3, 49, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("Up/Down, PageUp/PageDown to scroll, {} to exit", keymap.key_names(Action::MenuCancel)));
+
+    let newest = gamelog.entries.len() as i32 - scroll;
+    let oldest = i32::max(0, newest - PAGE_HEIGHT);
+    for (line, idx) in (oldest..newest).enumerate(){
+        draw_log_entry(context, 2, 2 + line as i32, &gamelog.entries[idx as usize]);
+    }
+
+    match context.key{
+        None => (ItemMenuResult::NoResponse, scroll),
+        Some(key) => match key{
+            key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, 0),
+            key if keymap.is_pressed(key, Action::MenuUp) => (ItemMenuResult::NoResponse, i32::min(scroll + 1, max_scroll)),
+            key if keymap.is_pressed(key, Action::MenuDown) => (ItemMenuResult::NoResponse, i32::max(scroll - 1, 0)),
+            VirtualKeyCode::PageUp => (ItemMenuResult::NoResponse, i32::min(scroll + PAGE_HEIGHT, max_scroll)),
+            VirtualKeyCode::PageDown => (ItemMenuResult::NoResponse, i32::max(scroll - PAGE_HEIGHT, 0)),
+            _ => (ItemMenuResult::NoResponse, scroll),
+        }
+    }
+}
+
 pub fn draw_tooltips(world: &World, context: &mut Rltk){
     let map = world.fetch::<Map>();
     let names = world.read_storage::<Name>();
     let positions = world.read_storage::<Position>();
+    let hidden = world.read_storage::<Hidden>();
 
     let mouse_position = context.mouse_pos();
-    if !Map::is_idx_valid(mouse_position.0, mouse_position.1){
+    if mouse_position.0 >= VIEWPORT_WIDTH || mouse_position.1 >= VIEWPORT_HEIGHT{
+        return;
+    }
+
+    // the mouse is on the screen, entities are on the map
+    let mouse_map_position = screen_to_map(world, Point::new(mouse_position.0, mouse_position.1));
+    if !map.is_idx_valid(mouse_map_position.x, mouse_map_position.y){
         return;
     }
 
     let mut tooltip = Vec::new();
-    for (name, position) in (&names, &positions).join(){
+    for (name, position, _hidden) in (&names, &positions, !&hidden).join(){
         let idx = map.xy_idx(position.x, position.y);
-        if position.x == mouse_position.0 && position.y == mouse_position.1 && map.currently_visible_tiles[idx]{
+        if position.x == mouse_map_position.x && position.y == mouse_map_position.y && map.currently_visible_tiles[idx]{
             tooltip.push(name.name.to_string());
         }
     }
@@ -125,10 +228,12 @@ pub enum ItemMenuResult{
 
 pub fn show_inventory(world: &mut World, context: &mut Rltk) -> (ItemMenuResult, Option<Entity>){
     let player_entity = world.fetch::<Entity>();
+    let keymap = world.fetch::<Keymap>();
     let names = world.read_storage::<Name>();
     let backpacks = world.read_storage::<InBackpack>();
     let entities = world.entities();
     let consumable_items = world.read_storage::<Consumable>();
+    let charged_items = world.read_storage::<Charges>();
 
     let number_of_items = (&backpacks, &names).join()
         .filter(|(backpack, _name)| backpack.owner == *player_entity)
@@ -138,29 +243,44 @@ pub fn show_inventory(world: &mut World, context: &mut Rltk) -> (ItemMenuResult,
 
     context.draw_box(15, y-2, 40, number_of_items+3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     context.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Inventory");
-    context.print_color(18, y+number_of_items as i32 + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Escape to exit");
+    context.print_color(18, y+number_of_items as i32 + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("{} to exit", keymap.key_names(Action::MenuCancel)));
 
     let mut item_menu: Vec<Entity> = Vec::new();
-    for (j, (name, _backpack, entity)) in (&names, &backpacks, &entities).join().enumerate(){
+    let mut clicked: Option<Entity> = None;
+    for (j, (name, _backpack, entity)) in (&names, &backpacks, &entities).join().filter(|item| item.1.owner == *player_entity).enumerate(){
+        if menu_row_hovered(context, 16, 54, y) && context.left_click{
+            clicked = Some(entity);
+        }
         context.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         context.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
         context.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
 
-        let mut item_string = name.name.clone();
+        context.print(21, y, &name.name);
+        let suffix_x = 21 + name.name.len() as i32;
         if let Some(consumable) = consumable_items.get(entity){
-            item_string += &format!(" - {} charge(s)", consumable.charges);
+            context.print_color(suffix_x, y, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), format!(" ({} use(s))", consumable.charges));
+        }
+        if let Some(charges) = charged_items.get(entity){
+            if charges.current > 0{
+                context.print_color(suffix_x, y, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), format!(" [{}/{}]", charges.current, charges.max));
+            }else{
+                context.print_color(suffix_x, y, RGB::named(rltk::DARKGRAY), RGB::named(rltk::BLACK), " [empty]");
+            }
         }
-        context.print(21, y, item_string);
 
         item_menu.push(entity);
 
         y += 1;
     }
 
+    if clicked.is_some(){
+        return (ItemMenuResult::Selected, clicked);
+    }
+
     match context.key{
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => match key{
-            VirtualKeyCode::Escape => (ItemMenuResult::Exit, None),
+            key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, None),
             _ => {
                 let selection = rltk::letter_to_option(key);
                 if 0 <= selection && selection < item_menu.len() as i32{
@@ -174,23 +294,96 @@ pub fn show_inventory(world: &mut World, context: &mut Rltk) -> (ItemMenuResult,
 }
 
 pub fn show_drop_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMenuResult, Option<Entity>){
+    backpack_item_menu(world, context, "Drop which item?")
+}
+
+/// Pick a known spell. Ones the player can't afford are greyed out but can still be picked
+pub fn show_cast_menu(world: &mut World, context: &mut Rltk) -> (ItemMenuResult, Option<Entity>){
+    let player_entity = *world.fetch::<Entity>();
+    let spell_menu = known_spells(world, player_entity);
+    let keymap = world.fetch::<Keymap>();
+    let names = world.read_storage::<Name>();
+    let spells = world.read_storage::<Spell>();
+    let mana = world.read_storage::<Mana>().get(player_entity).map(|m| m.current).unwrap_or(0);
+
+    let number_of_spells = spell_menu.len();
+    let y = (25 - (number_of_spells/2)) as i32;
+
+    context.draw_box(15, y-2, 40, number_of_spells+3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Cast which spell?");
+    context.print_color(18, y+number_of_spells as i32 + 1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("{} to exit", keymap.key_names(Action::MenuCancel)));
+
+    if spell_menu.is_empty(){
+        context.print_color(18, y, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), "You don't know any spells.");
+    }
+
+    let mut clicked: Option<Entity> = None;
+    for (j, spell) in spell_menu.iter().enumerate(){
+        let y = y + j as i32;
+        if menu_row_hovered(context, 16, 54, y) && context.left_click{
+            clicked = Some(*spell);
+        }
+        let cost = spells.get(*spell).map(|s| s.mana_cost).unwrap_or(0);
+        let colour = if cost <= mana { RGB::named(rltk::WHITE) } else { RGB::named(rltk::GRAY) };
+
+        context.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        context.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
+        context.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+        context.print_color(21, y, colour, RGB::named(rltk::BLACK), &names.get(*spell).unwrap().name);
+        context.print_color(42, y, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), format!("{} MP", cost));
+        if j < 9{
+            context.print_color(49, y, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), format!("[{}]", j + 1));
+        }
+    }
+
+    if clicked.is_some(){
+        return (ItemMenuResult::Selected, clicked);
+    }
+
+    match context.key{
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key{
+            key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if 0 <= selection && selection < spell_menu.len() as i32{
+                    return (ItemMenuResult::Selected, Some(spell_menu[selection as usize]));
+                }
+                (ItemMenuResult::NoResponse, None)
+            },
+        }
+    }
+}
+
+pub fn show_throw_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMenuResult, Option<Entity>){
+    backpack_item_menu(world, context, "Throw which item?")
+}
+
+/// Pick one of the items in the player's backpack. The artifact is left out, losing it would lose the run
+fn backpack_item_menu(world: &mut World, context: &mut Rltk, title: &str) -> (ItemMenuResult, Option<Entity>){
     let player_entity = world.fetch::<Entity>();
+    let keymap = world.fetch::<Keymap>();
     let names = world.read_storage::<Name>();
     let backpack = world.read_storage::<InBackpack>();
+    let artifacts = world.read_storage::<Artifact>();
     let entities = world.entities();
 
-    let inventory_count = (&names, &backpack).join()
-        .filter(|(_name, pack)| pack.owner == *player_entity)
+    let inventory_count = (&names, &backpack, !&artifacts).join()
+        .filter(|(_name, pack, _artifact)| pack.owner == *player_entity)
         .count() as i32;
 
     let mut y = 25 - (inventory_count/2);
 
     context.draw_box(15, y-2, 31, inventory_count+3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
-    context.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Drop which item?");
-    context.print_color(18, y+inventory_count+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Escape to cancel");
+    context.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    context.print_color(18, y+inventory_count+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("{} to cancel", keymap.key_names(Action::MenuCancel)));
 
     let mut items: Vec<Entity> = Vec::new();
-    for (j, (entity, _backpack, name)) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity).enumerate(){
+    let mut clicked: Option<Entity> = None;
+    for (j, (entity, _backpack, name, _artifact)) in (&entities, &backpack, &names, !&artifacts).join().filter(|item| item.1.owner == *player_entity).enumerate(){
+        if menu_row_hovered(context, 16, 45, y) && context.left_click{
+            clicked = Some(entity);
+        }
 
         context.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         context.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
@@ -203,11 +396,15 @@ pub fn show_drop_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMenuRe
         y+=1;
     }
 
+    if clicked.is_some(){
+        return (ItemMenuResult::Selected, clicked);
+    }
+
     match context.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => {
             match key {
-                VirtualKeyCode::Escape => (ItemMenuResult::Exit, None),
+                key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, None),
                 _ => {
                     let selection = rltk::letter_to_option(key);
                     if selection >= 0 && selection < inventory_count{
@@ -221,21 +418,29 @@ pub fn show_drop_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMenuRe
     }
 }
 
-pub fn show_ranged_targeting(world: &mut World, context: &mut Rltk, ranged_item_range: i32) -> (ItemMenuResult, Option<Point>){
+/// Pick a tile in range to aim at with the mouse, or cycle through the monsters in range.
+/// The line shows where the shot will stop and the shaded tiles what its blast will cover.
+/// Returns the target once picked, otherwise the tile being cycled to for the next frame
+pub fn show_ranged_targeting(world: &mut World, context: &mut Rltk, ranged_item_range: i32, radius: i32, cursor: Option<Point>) -> (ItemMenuResult, Option<Point>){
     let player_entity = world.fetch::<Entity>();
-    let player_point = world.fetch::<Point>();
+    let player_point = *world.fetch::<Point>();
     let fov = world.read_storage::<FOV>();
+    let keymap = world.fetch::<Keymap>();
+    let map = world.fetch::<Map>();
 
-    context.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Select a target");
+    context.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK),
+        format!("Select a target: {} to cycle, {} to fire, {} to cancel", keymap.key_names(Action::MenuNext), keymap.key_names(Action::MenuSelect), keymap.key_names(Action::MenuCancel)));
     
     let mut tiles_in_range: Vec<Point> = Vec::new();
     
     if let Some(player_fov) = fov.get(*player_entity){
         for visible_point in player_fov.visible_tiles.iter(){
-            let distance_to_tile = rltk::DistanceAlg::Pythagoras.distance2d(*player_point, *visible_point);
+            let distance_to_tile = rltk::DistanceAlg::Pythagoras.distance2d(player_point, *visible_point);
 
             if distance_to_tile < ranged_item_range as f32 {
-                context.set_bg(visible_point.x, visible_point.y, RGB::named(rltk::BLUE));
+                if let Some(screen_point) = map_to_screen(world, *visible_point){
+                    context.set_bg(screen_point.x, screen_point.y, RGB::named(rltk::BLUE));
+                }
                 tiles_in_range.push(*visible_point);
             }
         }
@@ -243,33 +448,94 @@ pub fn show_ranged_targeting(world: &mut World, context: &mut Rltk, ranged_item_
         return (ItemMenuResult::NoResponse, None);
     }
 
-    let mouse_position = context.mouse_point();
-    let is_selected_target_valid = tiles_in_range.iter()
-        .any(|tile| tile.x == mouse_position.x && tile.y == mouse_position.y);
+    // monsters in range, nearest first
+    let monsters = world.read_storage::<Monster>();
+    let hidden = world.read_storage::<Hidden>();
+    let mut monster_tiles: Vec<Point> = tiles_in_range.iter()
+        .filter(|tile| map.tile_content[map.xy_idx(tile.x, tile.y)].iter().any(|entity| monsters.get(*entity).is_some() && hidden.get(*entity).is_none()))
+        .copied()
+        .collect();
+    monster_tiles.sort_by_key(|tile| (tile.x - player_point.x).pow(2) + (tile.y - player_point.y).pow(2));
+
+    // targets are picked on screen but tiles in range are map positions
+    let mouse_screen_position = context.mouse_point();
+    let mouse_position = screen_to_map(world, mouse_screen_position);
+    let aim = cursor.unwrap_or(mouse_position);
+    let is_aim_valid = tiles_in_range.contains(&aim);
+
+    if is_aim_valid{
+        draw_projectile_preview(world, context, &map, player_point, aim, radius);
+    } else {
+        context.set_bg(mouse_screen_position.x, mouse_screen_position.y, RGB::named(rltk::RED));
+    }
 
-    if is_selected_target_valid{
-        context.set_bg(mouse_position.x, mouse_position.y, RGB::named(rltk::CYAN));
-        
-        if context.left_click{
+    if context.left_click{
+        if tiles_in_range.contains(&mouse_position){
             return (ItemMenuResult::Selected, Some(mouse_position));
         }
-    } else {
-        context.set_bg(mouse_position.x, mouse_position.y, RGB::named(rltk::RED));
+        return (ItemMenuResult::Exit, None);
+    }
 
-        if context.left_click{
-            return (ItemMenuResult::Exit, None);
+    match context.key{
+        None => (ItemMenuResult::NoResponse, cursor),
+        Some(key) => match key{
+            key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, None),
+            key if keymap.is_pressed(key, Action::MenuNext) => {
+                let next = match cursor.and_then(|cursor| monster_tiles.iter().position(|tile| *tile == cursor)){
+                    Some(i) => monster_tiles.get((i + 1) % monster_tiles.len()),
+                    None => monster_tiles.first(),
+                };
+                (ItemMenuResult::NoResponse, next.copied())
+            },
+            key if keymap.is_pressed(key, Action::MenuSelect) && is_aim_valid => (ItemMenuResult::Selected, Some(aim)),
+            _ => (ItemMenuResult::NoResponse, cursor),
         }
     }
+}
 
+/// Shade the line a shot flies along and the tiles its blast covers, warning when the player is caught in it
+fn draw_projectile_preview(world: &World, context: &mut Rltk, map: &Map, start: Point, target: Point, radius: i32){
+    let combat_stats = world.read_storage::<CombatStats>();
+    let path = map.projectile_path(start, target, |idx| map.tile_content[idx].iter().any(|entity| combat_stats.get(*entity).is_some()));
+    let impact = path.last().copied().unwrap_or(start);
 
-    (ItemMenuResult::NoResponse, None)
+    let shade = |context: &mut Rltk, point: Point, colour: (u8, u8, u8)|{
+        if let Some(screen_point) = map_to_screen(world, point){
+            context.set_bg(screen_point.x, screen_point.y, RGB::named(colour));
+        }
+    };
+
+    if radius > 0{
+        let blast: Vec<Point> = rltk::field_of_view(impact, radius, map).into_iter()
+            .filter(|point| map.is_idx_valid(point.x, point.y))
+            .collect();
+        for point in blast.iter(){
+            shade(context, *point, rltk::ORANGE);
+        }
+
+        if blast.contains(&start){
+            context.print_color(5, 1, RGB::named(rltk::RED), RGB::named(rltk::BLACK), "Warning: you are inside the blast!");
+        }
+    }
+
+    for point in path.iter(){
+        shade(context, *point, rltk::YELLOW);
+    }
+
+    // something in the way, the shot never gets there
+    if impact != target{
+        shade(context, target, rltk::GREY);
+        context.print_color(5, 2, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), "Something is in the way.");
+    }
+    shade(context, impact, rltk::CYAN);
 }
 
 pub fn main_menu(world: &mut World, context: &mut Rltk) -> MainMenuResult{
     let run_state = world.fetch::<RunState>();
+    let keymap = world.fetch::<Keymap>();
     let save_exists = does_save_exist();
-    // (new game selected?, load game selected?, quit selected?)
-    let mut options_are_selected:(bool, bool, bool) = (false, false, false);
+    // (new game selected?, load game selected?, high scores selected?, quit selected?)
+    let mut options_are_selected:(bool, bool, bool, bool) = (false, false, false, false);
 
     context.print_color_centered(15, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Rust Rougelike Tutorial");
 
@@ -281,33 +547,52 @@ pub fn main_menu(world: &mut World, context: &mut Rltk) -> MainMenuResult{
             MainMenuSelection::LoadGame => {
                 options_are_selected.1 = true;
             },
-            MainMenuSelection::Quit => {
+            MainMenuSelection::HighScores => {
                 options_are_selected.2 = true;
+            },
+            MainMenuSelection::Quit => {
+                options_are_selected.3 = true;
             }
         }
 
-        let (new_game_selected, load_game_selected, quit_selected) = options_are_selected;
+        let (new_game_selected, load_game_selected, high_scores_selected, quit_selected) = options_are_selected;
         main_menu_options_helper(context, new_game_selected, 24, "New Game");
 
         if save_exists{
             main_menu_options_helper(context, load_game_selected, 25, "Load game");
         }
 
-        main_menu_options_helper(context, quit_selected, 26, "Quit");
+        main_menu_options_helper(context, high_scores_selected, 26, "High Scores");
+        main_menu_options_helper(context, quit_selected, 27, "Quit");
+
+        // clicking an option picks it straight away
+        let mut option_rows = vec![(24, MainMenuSelection::NewGame)];
+        if save_exists{
+            option_rows.push((25, MainMenuSelection::LoadGame));
+        }
+        option_rows.push((26, MainMenuSelection::HighScores));
+        option_rows.push((27, MainMenuSelection::Quit));
+
+        for (y, option) in option_rows{
+            if menu_row_hovered(context, 32, 47, y) && context.left_click{
+                return MainMenuResult::Selected { selected: option };
+            }
+        }
     
 
         match context.key {
             None => return MainMenuResult::NoSelection { selected: selection },
             Some(key) => {
                 match key {
-                    VirtualKeyCode::Escape => return MainMenuResult::NoSelection { selected: MainMenuSelection::Quit },
-                    VirtualKeyCode::Up => {
+                    key if keymap.is_pressed(key, Action::MenuCancel) => return MainMenuResult::NoSelection { selected: MainMenuSelection::Quit },
+                    key if keymap.is_pressed(key, Action::MenuUp) => {
                         let mut new_selection;
 
                         match selection{
                             MainMenuSelection::NewGame => new_selection = MainMenuSelection::Quit,
                             MainMenuSelection::LoadGame => new_selection = MainMenuSelection::NewGame,
-                            MainMenuSelection::Quit => new_selection = MainMenuSelection::LoadGame,
+                            MainMenuSelection::HighScores => new_selection = MainMenuSelection::LoadGame,
+                            MainMenuSelection::Quit => new_selection = MainMenuSelection::HighScores,
                         }
 
                         if new_selection == MainMenuSelection::LoadGame && !save_exists{
@@ -316,22 +601,23 @@ pub fn main_menu(world: &mut World, context: &mut Rltk) -> MainMenuResult{
 
                         return MainMenuResult::NoSelection { selected: new_selection };
                     },
-                    VirtualKeyCode::Down =>{
+                    key if keymap.is_pressed(key, Action::MenuDown) =>{
                         let mut new_selection;
 
                         match selection {
                             MainMenuSelection::NewGame => new_selection = MainMenuSelection::LoadGame,
-                            MainMenuSelection::LoadGame => new_selection = MainMenuSelection::Quit,
+                            MainMenuSelection::LoadGame => new_selection = MainMenuSelection::HighScores,
+                            MainMenuSelection::HighScores => new_selection = MainMenuSelection::Quit,
                             MainMenuSelection::Quit => new_selection = MainMenuSelection::NewGame,
                         }
 
                         if new_selection == MainMenuSelection::LoadGame && !save_exists{
-                            new_selection = MainMenuSelection::Quit;
+                            new_selection = MainMenuSelection::HighScores;
                         }
 
                         return MainMenuResult::NoSelection { selected: new_selection };
                     },
-                    VirtualKeyCode::Return => return MainMenuResult::Selected { selected: selection },
+                    key if keymap.is_pressed(key, Action::MenuSelect) => return MainMenuResult::Selected { selected: selection },
                     _ => return MainMenuResult::NoSelection { selected: selection }
                 }
             }
@@ -341,6 +627,32 @@ pub fn main_menu(world: &mut World, context: &mut Rltk) -> MainMenuResult{
     MainMenuResult::NoSelection { selected: MainMenuSelection::Quit }
 }
 
+/// Table of the best runs, any key goes back to the main menu
+pub fn show_high_scores(world: &World, context: &mut Rltk) -> ItemMenuResult{
+    let high_scores = &world.fetch::<HighScoreTable>().scores;
+
+    context.draw_box(10, 10, 60, 20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color_centered(10, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "High Scores");
+    context.print_color(12, 12, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Score  Depth  Kills  Gold  Ending");
+
+    if high_scores.is_empty(){
+        context.print(12, 14, "No runs recorded yet.");
+    }
+
+    for (i, high_score) in high_scores.iter().enumerate(){
+        let colour = if high_score.victory { RGB::named(rltk::GOLD) } else { RGB::named(rltk::WHITE) };
+        context.print_color(12, 14 + i as i32, colour, RGB::named(rltk::BLACK),
+            format!("{:>5}  {:>5}  {:>5}  {:>4}  {}", high_score.score, high_score.depth, high_score.kills, high_score.gold, high_score.ending));
+    }
+
+    context.print_color_centered(30, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press any key to go back");
+
+    match context.key{
+        None => ItemMenuResult::NoResponse,
+        Some(_) => ItemMenuResult::Exit,
+    }
+}
+
 fn main_menu_options_helper(context: &mut Rltk, is_selected: bool, y: i32, option_name: &str){
     let option_highlight: RGB;
     if is_selected{
@@ -354,6 +666,7 @@ fn main_menu_options_helper(context: &mut Rltk, is_selected: bool, y: i32, optio
 
 pub fn show_unequip_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMenuResult, Option<Entity>){
     let player_entity = world.fetch::<Entity>();
+    let keymap = world.fetch::<Keymap>();
     let names = world.read_storage::<Name>();
     let equipped_items = world.read_storage::<Equipped>();
     let entities = world.entities();
@@ -366,10 +679,14 @@ pub fn show_unequip_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMen
 
     context.draw_box(15, y-2, 31, inventory_count+3, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     context.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Remove which item?");
-    context.print_color(18, y+inventory_count+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Escape to cancel");
+    context.print_color(18, y+inventory_count+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("{} to cancel", keymap.key_names(Action::MenuCancel)));
 
     let mut equippable: Vec<Entity> = Vec::new();
+    let mut clicked: Option<Entity> = None;
     for (j, (entity, _equip, name)) in (&entities, &equipped_items, &names).join().filter(|item| item.1.owner == *player_entity).enumerate(){
+        if menu_row_hovered(context, 16, 45, y) && context.left_click{
+            clicked = Some(entity);
+        }
 
         context.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
         context.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
@@ -382,11 +699,15 @@ pub fn show_unequip_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMen
         y+=1;
     }
 
+    if clicked.is_some(){
+        return (ItemMenuResult::Selected, clicked);
+    }
+
     match context.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => {
             match key {
-                VirtualKeyCode::Escape => (ItemMenuResult::Exit, None),
+                key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, None),
                 _ => {
                     let selection = rltk::letter_to_option(key);
                     if selection >= 0 && selection < inventory_count{
@@ -400,19 +721,459 @@ pub fn show_unequip_item_menu(world: &mut World, context: &mut Rltk) -> (ItemMen
     }
 }
 
+#[derive(PartialEq, Clone, Copy)]
+pub enum ShopMode{
+    Buy,
+    Sell,
+}
+
+/// Buy from or sell to a shopkeeper. Tab switches between buying and selling,
+/// the returned mode is the one to show next frame
+pub fn show_shop(world: &mut World, context: &mut Rltk, vendor: Entity, mode: ShopMode) -> (ItemMenuResult, Option<Entity>, ShopMode){
+    let player_entity = *world.fetch::<Entity>();
+    let keymap = world.fetch::<Keymap>();
+
+    // whoever is selling owns the listed items
+    let seller = match mode{
+        ShopMode::Buy => vendor,
+        ShopMode::Sell => player_entity,
+    };
+
+    let mut stock: Vec<(Entity, String, i32)> = Vec::new();
+    {
+        let names = world.read_storage::<Name>();
+        let backpacks = world.read_storage::<InBackpack>();
+        let artifacts = world.read_storage::<Artifact>();
+        let entities = world.entities();
+
+        // the artifact is what the run is for, it is never up for sale
+        for (entity, _backpack, name, _artifact) in (&entities, &backpacks, &names, !&artifacts).join().filter(|item| item.1.owner == seller){
+            stock.push((entity, name.name.clone(), 0));
+        }
+    }
+
+    for (item, _name, price) in stock.iter_mut(){
+        *price = match mode{
+            ShopMode::Buy => item_price(world, *item),
+            ShopMode::Sell => item_sell_price(world, *item),
+        };
+    }
+
+    let (player_gold, vendor_gold) = {
+        let wallets = world.read_storage::<Wallet>();
+        (wallets.get(player_entity).map_or(0, |w| w.gold), wallets.get(vendor).map_or(0, |w| w.gold))
+    };
+
+    let stock_count = stock.len() as i32;
+    let top_y = 25 - (stock_count/2);
+    let title = match mode{
+        ShopMode::Buy => "Buy which item?",
+        ShopMode::Sell => "Sell which item?",
+    };
+
+    context.draw_box(15, top_y-3, 45, stock_count+4, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color(18, top_y-3, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+    context.print_color(18, top_y-2, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), format!("You: {} gold  Shop: {} gold", player_gold, vendor_gold));
+    context.print_color(18, top_y+stock_count+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("{} to switch, {} to leave", keymap.key_names(Action::MenuNext), keymap.key_names(Action::MenuCancel)));
+
+    for (j, (_item, name, price)) in stock.iter().enumerate(){
+        let y = top_y + j as i32;
+        context.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        context.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
+        context.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+
+        context.print(21, y, name);
+        context.print_color(50, y, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), format!("{} gold", price));
+    }
+
+    match context.key {
+        None => (ItemMenuResult::NoResponse, None, mode),
+        Some(key) => {
+            match key {
+                key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, None, mode),
+                key if keymap.is_pressed(key, Action::MenuNext) => {
+                    let next_mode = match mode{
+                        ShopMode::Buy => ShopMode::Sell,
+                        ShopMode::Sell => ShopMode::Buy,
+                    };
+                    (ItemMenuResult::NoResponse, None, next_mode)
+                },
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection >= 0 && selection < stock_count{
+                        return (ItemMenuResult::Selected, Some(stock[selection as usize].0), mode);
+                    }
+
+                    (ItemMenuResult::NoResponse, None, mode)
+                }
+            }
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum GameOverResult{
     NoSelection,
     GoToMainMenu,
 }
 
-pub fn game_over(context: &mut Rltk) -> GameOverResult{
-    context.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "You ded.");
+pub fn game_over(world: &World, context: &mut Rltk) -> GameOverResult{
+    let run_stats = world.fetch::<RunStats>();
 
-    context.print_color_centered(20, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to main menu");
+    context.draw_box(15, 10, 50, 20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color_centered(12, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "You ded.");
+
+    for (i, line) in run_stats.summary().iter().enumerate(){
+        context.print(17, 14 + i as i32, line);
+    }
+
+    context.print_color(17, 22, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Kills:");
+    for (i, (name, count)) in run_stats.kills.iter().take(6).enumerate(){
+        context.print(19, 23 + i as i32, format!("{} x{}", name, count));
+    }
+
+    context.print_color_centered(29, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to main menu");
 
     match context.key{
         None => GameOverResult::NoSelection,
         Some(_) => GameOverResult::GoToMainMenu,
     }
-}
\ No newline at end of file
+}
+/// Shown after carrying the artifact back to the town, with a short summary of how the run ended
+pub fn victory(world: &World, context: &mut Rltk) -> GameOverResult{
+    let player_entity = world.fetch::<Entity>();
+    let combat_stats = world.read_storage::<CombatStats>();
+    let wallets = world.read_storage::<Wallet>();
+    let backpacks = world.read_storage::<InBackpack>();
+    let equipped = world.read_storage::<Equipped>();
+    let names = world.read_storage::<Name>();
+
+    context.draw_box(15, 10, 50, 20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color_centered(12, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), "Victory!");
+    context.print_color_centered(13, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "You return to the town with the Ember Crown.");
+
+    if let Some(stats) = combat_stats.get(*player_entity){
+        context.print_color(17, 15, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), format!("Health: {} / {}", stats.hp, stats.max_hp));
+    }
+    if let Some(wallet) = wallets.get(*player_entity){
+        context.print_color(17, 16, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("Gold: {}", wallet.gold));
+    }
+
+    let run_stats = world.fetch::<RunStats>();
+    for (i, line) in run_stats.summary().iter().enumerate(){
+        context.print(17, 17 + i as i32, line);
+    }
+
+    context.print_color(17, 24, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Carrying:");
+    let carried = (&names, &backpacks).join()
+        .filter(|(_name, backpack)| backpack.owner == *player_entity)
+        .map(|(name, _backpack)| name.name.clone())
+        .chain((&names, &equipped).join()
+            .filter(|(_name, equip)| equip.owner == *player_entity)
+            .map(|(name, _equip)| format!("{} (equipped)", name.name)));
+    for (i, name) in carried.take(4).enumerate(){
+        context.print(19, 25 + i as i32, name);
+    }
+
+    context.print_color_centered(29, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Press any key to return to main menu");
+
+    match context.key{
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::GoToMainMenu,
+    }
+}
+
+/// Visible named things other than the player, nearest first. Look mode cycles through these
+pub fn visible_entities(world: &World) -> Vec<Entity>{
+    let entities = world.entities();
+    let player_entity = world.fetch::<Entity>();
+    let player_point = world.fetch::<Point>();
+    let map = world.fetch::<Map>();
+    let names = world.read_storage::<Name>();
+    let positions = world.read_storage::<Position>();
+    let hidden = world.read_storage::<Hidden>();
+
+    let mut visible: Vec<(Entity, f32)> = (&entities, &names, &positions, !&hidden).join()
+        .filter(|(entity, _name, pos, _hidden)| *entity != *player_entity && map.currently_visible_tiles[map.xy_idx(pos.x, pos.y)])
+        .map(|(entity, _name, pos, _hidden)| (entity, rltk::DistanceAlg::Pythagoras.distance2d(*player_point, Point::new(pos.x, pos.y))))
+        .collect();
+    visible.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    visible.into_iter().map(|(entity, _distance)| entity).collect()
+}
+
+/// (power, defense) an entity gets from what it has equipped
+fn equipment_bonuses(world: &World, owner: Entity) -> (i32, i32){
+    let equipped = world.read_storage::<Equipped>();
+    let power_bonuses = world.read_storage::<MeleePowerBonus>();
+    let defense_bonuses = world.read_storage::<DefenseBonus>();
+
+    let mut bonuses = (0, 0);
+    for (equip, entity) in (&equipped, &world.entities()).join(){
+        if equip.owner == owner{
+            bonuses.0 += power_bonuses.get(entity).map(|bonus| bonus.power).unwrap_or(0);
+            bonuses.1 += defense_bonuses.get(entity).map(|bonus| bonus.defense).unwrap_or(0);
+        }
+    }
+    bonuses
+}
+
+/// Rough word for how a melee fight with the monster would go, by how many hits each side needs
+fn danger_estimate(world: &World, monster: Entity) -> (&'static str, RGB){
+    let player_entity = *world.fetch::<Entity>();
+    let combat_stats = world.read_storage::<CombatStats>();
+    let (Some(player_stats), Some(monster_stats)) = (combat_stats.get(player_entity), combat_stats.get(monster)) else {
+        return ("Unknown", RGB::named(rltk::GREY));
+    };
+
+    let (player_power, player_defense) = equipment_bonuses(world, player_entity);
+    let (monster_power, monster_defense) = equipment_bonuses(world, monster);
+
+    let damage_to_player = i32::max(0, monster_stats.attack + monster_power - player_stats.defense - player_defense);
+    let damage_to_monster = i32::max(0, player_stats.attack + player_power - monster_stats.defense - monster_defense);
+    if damage_to_player == 0{
+        return ("Harmless", RGB::named(rltk::GREEN));
+    }
+    if damage_to_monster == 0{
+        return ("Deadly", RGB::named(rltk::RED));
+    }
+
+    let hits_to_kill_player = (player_stats.hp + damage_to_player - 1) / damage_to_player;
+    let hits_to_kill_monster = (monster_stats.hp + damage_to_monster - 1) / damage_to_monster;
+    if hits_to_kill_monster <= 1{
+        ("Trivial", RGB::named(rltk::GREEN))
+    } else if hits_to_kill_player <= hits_to_kill_monster{
+        ("Deadly", RGB::named(rltk::RED))
+    } else if hits_to_kill_player <= hits_to_kill_monster * 2{
+        ("Dangerous", RGB::named(rltk::ORANGE))
+    } else {
+        ("Manageable", RGB::named(rltk::YELLOW))
+    }
+}
+
+fn health_description(stats: &CombatStats) -> (&'static str, RGB){
+    let health = stats.hp as f32 / stats.max_hp as f32;
+    if health >= 1.0{
+        ("Unhurt", RGB::named(rltk::GREEN))
+    } else if health > 0.66{
+        ("Lightly wounded", RGB::named(rltk::YELLOW))
+    } else if health > 0.33{
+        ("Badly wounded", RGB::named(rltk::ORANGE))
+    } else {
+        ("Nearly dead", RGB::named(rltk::RED))
+    }
+}
+
+/// Lines describing an item's stats, compared against whatever the player has in the same slot
+fn item_description(world: &World, item: Entity) -> Vec<(String, RGB)>{
+    let white = RGB::named(rltk::WHITE);
+    let mut lines = Vec::new();
+
+    if let Some(healing) = world.read_storage::<ProvidesHealing>().get(item){
+        lines.push((format!("Heals {} hp", healing.heal_amount), RGB::named(rltk::GREEN)));
+    }
+    if let Some(damage) = world.read_storage::<InflictsDamage>().get(item){
+        lines.push((format!("Deals {} {} damage", damage.damage, damage.damage_type.name()), RGB::named(rltk::RED)));
+    }
+    if let Some(aoe) = world.read_storage::<AreaOfEffect>().get(item){
+        lines.push((format!("Radius {}", aoe.radius), white));
+    }
+    if let Some(ranged) = world.read_storage::<Ranged>().get(item){
+        lines.push((format!("Range {}", ranged.range), white));
+    }
+    if let Some(confusion) = world.read_storage::<CausesConfusion>().get(item){
+        lines.push((format!("Confuses for {} turns", confusion.turns), white));
+    }
+    if let Some(consumable) = world.read_storage::<Consumable>().get(item){
+        lines.push((format!("{} use(s), gone when used up", consumable.charges), white));
+    }
+    if let Some(teaches) = world.read_storage::<TeachesSpell>().get(item){
+        lines.push((format!("Teaches {}", teaches.spell), RGB::named(rltk::CYAN)));
+    }
+    if let Some(charges) = world.read_storage::<Charges>().get(item){
+        lines.push((format!("{}/{} charges, kept when empty", charges.current, charges.max), RGB::named(rltk::CYAN)));
+    }
+    if let Some(recharge) = world.read_storage::<RechargesOverTime>().get(item){
+        lines.push((format!("Regains a charge every {} turns", recharge.turns), white));
+    }
+
+    lines.extend(damage_trait_lines(world, item));
+
+    if let Some(equippable) = world.read_storage::<Equippable>().get(item){
+        let power_bonuses = world.read_storage::<MeleePowerBonus>();
+        let defense_bonuses = world.read_storage::<DefenseBonus>();
+        let power = power_bonuses.get(item).map(|bonus| bonus.power).unwrap_or(0);
+        let defense = defense_bonuses.get(item).map(|bonus| bonus.defense).unwrap_or(0);
+        lines.push((format!("Power +{}, defense +{}", power, defense), white));
+
+        let player_entity = *world.fetch::<Entity>();
+        let names = world.read_storage::<Name>();
+        let equipped = world.read_storage::<Equipped>();
+        let current = (&world.entities(), &equipped).join()
+            .find(|(_entity, equip)| equip.owner == player_entity && equip.slot == equippable.slot);
+
+        match current{
+            None => lines.push(("Nothing equipped in that slot".to_string(), RGB::named(rltk::GREEN))),
+            Some((current_item, _equip)) => {
+                let power_change = power - power_bonuses.get(current_item).map(|bonus| bonus.power).unwrap_or(0);
+                let defense_change = defense - defense_bonuses.get(current_item).map(|bonus| bonus.defense).unwrap_or(0);
+                let colour = match power_change + defense_change{
+                    change if change > 0 => RGB::named(rltk::GREEN),
+                    change if change < 0 => RGB::named(rltk::RED),
+                    _ => white,
+                };
+                let current_name = names.get(current_item).map(|name| name.name.clone()).unwrap_or_default();
+                lines.push((format!("vs {}: power {:+}, defense {:+}", current_name, power_change, defense_change), colour));
+            }
+        }
+    }
+
+    lines
+}
+
+/// The kind of damage something deals in melee and what it resists, ignores or fears
+fn damage_trait_lines(world: &World, entity: Entity) -> Vec<(String, RGB)>{
+    let list = |types: &[DamageType]| types.iter().map(|t| t.name()).collect::<Vec<&str>>().join(", ");
+    let mut lines = Vec::new();
+
+    if let Some(melee) = world.read_storage::<MeleeDamageType>().get(entity){
+        lines.push((format!("Strikes with {}", melee.damage_type.name()), RGB::named(rltk::ORANGE)));
+    }
+    if let Some(resistant) = world.read_storage::<Resistant>().get(entity){
+        if !resistant.resisted.is_empty(){
+            lines.push((format!("Resists {}", list(&resistant.resisted)), RGB::named(rltk::CYAN)));
+        }
+        if !resistant.immune.is_empty(){
+            lines.push((format!("Immune to {}", list(&resistant.immune)), RGB::named(rltk::CYAN)));
+        }
+    }
+    if let Some(vulnerable) = world.read_storage::<Vulnerable>().get(entity){
+        lines.push((format!("Vulnerable to {}", list(&vulnerable.damage_types)), RGB::named(rltk::RED)));
+    }
+    if let Some(thorns) = world.read_storage::<Thorns>().get(entity){
+        lines.push((format!("Pricks attackers for {} damage", thorns.damage), RGB::named(rltk::ORANGE)));
+    }
+
+    lines
+}
+
+/// Lines describing a creature: health, status effects, gear and how dangerous it looks
+fn creature_description(world: &World, creature: Entity) -> Vec<(String, RGB)>{
+    let white = RGB::named(rltk::WHITE);
+    let mut lines = Vec::new();
+
+    if let Some(stats) = world.read_storage::<CombatStats>().get(creature){
+        let (health, colour) = health_description(stats);
+        lines.push((health.to_string(), colour));
+    }
+
+    if let Some(confused) = world.read_storage::<IsConfused>().get(creature){
+        lines.push((format!("Confused ({} turns)", confused.turns), RGB::named(rltk::MAGENTA)));
+    }
+    if world.read_storage::<Burning>().get(creature).is_some(){
+        lines.push(("Burning".to_string(), RGB::named(rltk::ORANGE)));
+    }
+    if world.read_storage::<Alerted>().get(creature).is_some(){
+        lines.push(("Alerted".to_string(), RGB::named(rltk::ORANGE)));
+    }
+    if let Some(grudge) = world.read_storage::<Grudge>().get(creature){
+        let target = world.read_storage::<Name>().get(grudge.target).map(|n| n.name.clone()).unwrap_or_default();
+        lines.push((format!("Angry at {} ({} turns)", target, grudge.turns), RGB::named(rltk::RED)));
+    }
+    if let Some(speed) = world.read_storage::<HasMovementSpeedModifier>().get(creature){
+        lines.push((format!("Moving at x{} speed", speed.speed_modifier), RGB::named(rltk::CYAN)));
+    }
+
+    let names = world.read_storage::<Name>();
+    let equipped = world.read_storage::<Equipped>();
+    for (name, equip) in (&names, &equipped).join(){
+        if equip.owner == creature{
+            lines.push((format!("Wields {}", name.name), white));
+        }
+    }
+
+    lines.extend(damage_trait_lines(world, creature));
+
+    if world.read_storage::<Monster>().get(creature).is_some(){
+        let (danger, colour) = danger_estimate(world, creature);
+        lines.push((format!("Danger: {}", danger), colour));
+    }
+
+    lines
+}
+
+/// Keyboard look mode. Tab or the arrow keys cycle through what the player can see,
+/// the chosen thing is highlighted and described in a panel. Returns the index to look at next frame
+pub fn show_look(world: &World, context: &mut Rltk, index: usize) -> (ItemMenuResult, usize){
+    let visible = visible_entities(world);
+    let keymap = world.fetch::<Keymap>();
+    context.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("Looking around: {} to cycle, {} to exit", keymap.key_names(Action::MenuNext), keymap.key_names(Action::MenuCancel)));
+
+    if visible.is_empty(){
+        context.print_color(5, 1, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "You see nothing of interest.");
+        return match context.key{
+            None => (ItemMenuResult::NoResponse, 0),
+            Some(_) => (ItemMenuResult::Exit, 0),
+        };
+    }
+
+    let index = index % visible.len();
+    let target = visible[index];
+    let positions = world.read_storage::<Position>();
+    let names = world.read_storage::<Name>();
+
+    if let Some(pos) = positions.get(target){
+        if let Some(screen_point) = map_to_screen(world, Point::new(pos.x, pos.y)){
+            context.set_bg(screen_point.x, screen_point.y, RGB::named(rltk::CYAN));
+        }
+    }
+
+    let mut lines = if world.read_storage::<Item>().get(target).is_some(){
+        item_description(world, target)
+    } else if world.read_storage::<EntryTrigger>().get(target).is_some(){
+        vec![("A trap, triggers when stepped on".to_string(), RGB::named(rltk::ORANGE))]
+    } else {
+        creature_description(world, target)
+    };
+    let name = names.get(target).map(|name| name.name.clone()).unwrap_or_default();
+    lines.insert(0, (name, RGB::named(rltk::YELLOW)));
+
+    let panel_width = lines.iter().map(|(line, _colour)| line.len()).max().unwrap_or(0) as i32 + 3;
+    context.draw_box(1, 2, panel_width, lines.len() as i32 + 1, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    for (i, (line, colour)) in lines.iter().enumerate(){
+        context.print_color(3, 3 + i as i32, *colour, RGB::named(rltk::BLACK), line);
+    }
+
+    match context.key{
+        None => (ItemMenuResult::NoResponse, index),
+        Some(key) => match key{
+            key if keymap.is_pressed(key, Action::MenuCancel) => (ItemMenuResult::Exit, 0),
+            key if keymap.is_pressed(key, Action::MenuNext) || keymap.is_pressed(key, Action::MenuDown) => (ItemMenuResult::NoResponse, (index + 1) % visible.len()),
+            key if keymap.is_pressed(key, Action::MenuUp) => (ItemMenuResult::NoResponse, (index + visible.len() - 1) % visible.len()),
+            _ => (ItemMenuResult::NoResponse, index),
+        }
+    }
+}
+
+/// Every action and the keys bound to it, from the keymap file
+pub fn show_help(world: &World, context: &mut Rltk) -> ItemMenuResult{
+    let keymap = world.fetch::<Keymap>();
+
+    context.draw_box(10, 2, 60, Action::ALL.len() as i32 + 5, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    context.print_color_centered(2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("Key Bindings ({:?})", keymap.preset));
+
+    for (i, action) in Action::ALL.iter().enumerate(){
+        let y = 4 + i as i32;
+        context.print(13, y, action.description());
+        context.print_color(36, y, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), keymap.key_names(*action));
+    }
+    context.print(13, Action::ALL.len() as i32 + 5, "Shift+move runs, click travels, 1-9 quick casts");
+
+    context.print_color_centered(Action::ALL.len() as i32 + 7, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK),
+        format!("Edit {} to rebind, any key to go back", KEYMAP_FILE_PATH));
+
+    match context.key{
+        None => ItemMenuResult::NoResponse,
+        Some(_) => ItemMenuResult::Exit,
+    }
+}
@@ -0,0 +1,183 @@
+use specs::prelude::*;
+use rltk::RandomNumberGenerator;
+
+use crate::{Map, TileType, Position, Name, GameLog, LogEntry, SuffersDamage, DamageInstance, DamageType, Burning, FOV, RunState, CombatStats, Hidden};
+
+pub const FIRE_DAMAGE: i32 = 4;
+pub const BURNING_DAMAGE: i32 = 2;
+pub const BURNING_TURNS: i32 = 3;
+pub const GAS_DAMAGE: i32 = 2;
+pub const SMOKE_FROM_FIRE: i32 = 4;
+pub const LAVA_DAMAGE: i32 = 5;
+
+/// Simulates fire, poison gas and smoke left on the map once per turn
+pub struct HazardSystem{ }
+
+impl<'a> System<'a> for HazardSystem{
+    type SystemData = ( WriteExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        ReadExpect<'a, RunState>,
+                        Entities<'a>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SuffersDamage>,
+                        WriteStorage<'a, Burning>,
+                        WriteStorage<'a, FOV>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Hidden>,
+                        ReadExpect<'a, Entity>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, mut rng, run_state, entities, positions, names, mut suffers_damage, mut burning, mut fov, mut gamelog, combat_stats, hidden, player_entity) = data;
+
+        // hazards advance once per full turn, alongside the monsters
+        if *run_state != RunState::MonsterTurn{ return; }
+
+        let had_smoke = map.smoke.iter().any(|s| *s > 0);
+
+        // only tell the player about what happens to them or in their sight
+        let seen = |entity: Entity, pos: &Position| entity == *player_entity || fov.get(*player_entity)
+            .is_some_and(|player_fov| player_fov.visible_tiles.contains(&rltk::Point::new(pos.x, pos.y)));
+
+        // creatures standing in fire catch light, in gas choke and in lava burn, for every turn they stay there
+        for (entity, pos, _stats, _hidden) in (&entities, &positions, &combat_stats, !&hidden).join(){
+            let idx = map.xy_idx(pos.x, pos.y);
+
+            if map.tiles[idx] == TileType::Lava{
+                SuffersDamage::new_damage(&mut suffers_damage, entity, hazard_damage(LAVA_DAMAGE, DamageType::Fire, "lava"));
+                if let (Some(name), true) = (names.get(entity), seen(entity, pos)){
+                    gamelog.add(LogEntry::new().name(&name.name).text(" is burned by lava!"));
+                }
+            }
+
+            if map.fire[idx] > 0{
+                SuffersDamage::new_damage(&mut suffers_damage, entity, hazard_damage(FIRE_DAMAGE, DamageType::Fire, "fire"));
+                burning.insert(entity, Burning { turns: BURNING_TURNS })
+                    .expect("Could not set entity on fire.");
+            }
+
+            if map.gas[idx] > 0{
+                SuffersDamage::new_damage(&mut suffers_damage, entity, hazard_damage(GAS_DAMAGE, DamageType::Poison, "poison gas"));
+                if let (Some(name), true) = (names.get(entity), seen(entity, pos)){
+                    gamelog.add(LogEntry::new().name(&name.name).text(" chokes on poison gas."));
+                }
+            }
+        }
+
+        // burning entities keep taking damage until the fire goes out or they reach water
+        let mut extinguished: Vec<Entity> = Vec::new();
+        for (entity, burn, pos) in (&entities, &mut burning, &positions).join(){
+            let idx = map.xy_idx(pos.x, pos.y);
+
+            if matches!(map.tiles[idx], TileType::ShallowWater | TileType::DeepWater){
+                extinguished.push(entity);
+                if let (Some(name), true) = (names.get(entity), seen(entity, pos)){
+                    gamelog.add(LogEntry::new().name(&name.name).text(" douses the flames in the water."));
+                }
+                continue;
+            }
+
+            SuffersDamage::new_damage(&mut suffers_damage, entity, hazard_damage(BURNING_DAMAGE, DamageType::Fire, "burning"));
+            burn.turns -= 1;
+            if burn.turns <= 0{
+                extinguished.push(entity);
+            }
+        }
+        for entity in extinguished{
+            burning.remove(entity);
+        }
+
+        spread_fire(&mut map, &mut rng);
+        map.gas = drift(&map, &map.gas, &mut rng);
+        map.smoke = drift(&map, &map.smoke, &mut rng);
+
+        // smoke changes what can be seen, so everyone needs to look again
+        if had_smoke || map.smoke.iter().any(|s| *s > 0){
+            for fov in (&mut fov).join(){
+                fov.needs_update = true;
+            }
+        }
+    }
+}
+
+/// Fire jumps to neighbouring grass, burns down and leaves smoke behind
+fn spread_fire(map: &mut Map, rng: &mut RandomNumberGenerator){
+    let mut new_fires: Vec<usize> = Vec::new();
+
+    for idx in 0..map.fire.len(){
+        if map.fire[idx] <= 0{
+            continue;
+        }
+
+        let x = (idx % map.width as usize) as i32;
+        let y = (idx / map.width as usize) as i32;
+        for (nx, ny) in [(x-1, y), (x+1, y), (x, y-1), (x, y+1)]{
+            if nx < 0 || nx >= map.width || ny < 0 || ny >= map.height{
+                continue;
+            }
+
+            let next_idx = map.xy_idx(nx, ny);
+            let is_grass = matches!(map.tiles[next_idx], TileType::Grass | TileType::TallGrass);
+            if is_grass && map.fire[next_idx] == 0 && rng.roll_dice(1, 3) == 1{
+                new_fires.push(next_idx);
+            }
+        }
+
+        map.fire[idx] -= 1;
+        if map.fire[idx] == 0{
+            if matches!(map.tiles[idx], TileType::Grass | TileType::TallGrass){
+                map.tiles[idx] = TileType::Floor;
+            }
+            map.smoke[idx] = SMOKE_FROM_FIRE;
+        }
+    }
+
+    for idx in new_fires{
+        ignite(map, idx, 3);
+    }
+}
+
+/// Damage from the map itself, nobody gets the credit
+pub fn hazard_damage(amount: i32, damage_type: DamageType, cause: &str) -> DamageInstance{
+    DamageInstance{ amount, damage_type, source: None, item: None, cause: cause.to_string(), melee: false }
+}
+
+/// Set a tile alight, unless it is something that can't burn like water or stone
+pub fn ignite(map: &mut Map, idx: usize, turns: i32){
+    if map.can_burn(idx){
+        map.fire[idx] = i32::max(map.fire[idx], turns);
+    }
+}
+
+/// Thin out a cloud by one each turn while pushing half of every thick tile to a random open neighbour
+fn drift(map: &Map, cloud: &[i32], rng: &mut RandomNumberGenerator) -> Vec<i32>{
+    let mut next = vec![0; cloud.len()];
+
+    for (idx, density) in cloud.iter().enumerate(){
+        let remaining = *density - 1;
+        if remaining <= 0{
+            continue;
+        }
+
+        let x = (idx % map.width as usize) as i32;
+        let y = (idx / map.width as usize) as i32;
+        let neighbours: Vec<usize> = [(x-1, y), (x+1, y), (x, y-1), (x, y+1)].iter()
+            .filter(|(nx, ny)| *nx >= 0 && *nx < map.width && *ny >= 0 && *ny < map.height)
+            .map(|(nx, ny)| map.xy_idx(*nx, *ny))
+            .filter(|next_idx| map.can_hold_gas(*next_idx))
+            .collect();
+
+        if remaining >= 2 && !neighbours.is_empty(){
+            let drifted = remaining / 2;
+            let target = neighbours[rng.range(0, neighbours.len())];
+            next[target] = i32::max(next[target], drifted);
+            next[idx] = i32::max(next[idx], remaining - drifted);
+        } else {
+            next[idx] = i32::max(next[idx], remaining);
+        }
+    }
+
+    next
+}
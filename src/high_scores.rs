@@ -0,0 +1,80 @@
+use std::{fs::{self, read_to_string}, path::Path};
+
+use serde::{Serialize, Deserialize};
+use specs::prelude::*;
+
+use crate::{RunStats, Wallet, GameLog};
+
+pub const HIGH_SCORE_FILE_PATH: &str = "./high_scores.json";
+/// Only the best runs are kept
+pub const MAX_HIGH_SCORES: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HighScore{
+    pub score: i32,
+    pub depth: i32,
+    pub kills: i32,
+    pub gold: i32,
+    pub victory: bool,
+    /// how the run ended, the cause of death or the victory
+    pub ending: String,
+}
+
+/// The table read from disk when the high score screen was opened, so it isn't parsed every frame
+#[derive(Default)]
+pub struct HighScoreTable{
+    pub scores: Vec<HighScore>,
+}
+
+/// Depth counts the most, a win outweighs everything else
+pub fn score_run(depth: i32, kills: i32, gold: i32, victory: bool) -> i32{
+    let victory_bonus = if victory { 5000 } else { 0 };
+    depth * 100 + kills * 10 + gold + victory_bonus
+}
+
+/// Best runs so far, highest score first. A missing or unreadable file means no scores yet
+pub fn load_high_scores() -> Vec<HighScore>{
+    if !Path::new(HIGH_SCORE_FILE_PATH).exists(){
+        return Vec::new();
+    }
+
+    read_to_string(HIGH_SCORE_FILE_PATH)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Score the run that just ended and add it to the table if it made the cut
+pub fn record_high_score(world: &World, victory: bool){
+    let run_stats = world.fetch::<RunStats>();
+    let player_entity = world.fetch::<Entity>();
+    let gold = world.read_storage::<Wallet>().get(*player_entity)
+        .map(|wallet| wallet.gold)
+        .unwrap_or(0);
+
+    let kills = run_stats.total_kills();
+    let ending = match (&run_stats.cause_of_death, victory){
+        (_, true) => "Returned with the Ember Crown".to_string(),
+        (Some(cause), false) => format!("Killed by {}", cause),
+        (None, false) => "Died".to_string(),
+    };
+
+    let mut high_scores = load_high_scores();
+    high_scores.push(HighScore{
+        score: score_run(run_stats.deepest_depth, kills, gold, victory),
+        depth: run_stats.deepest_depth,
+        kills,
+        gold,
+        victory,
+        ending,
+    });
+    high_scores.sort_by_key(|high_score| std::cmp::Reverse(high_score.score));
+    high_scores.truncate(MAX_HIGH_SCORES);
+
+    let data = serde_json::to_string(&high_scores)
+        .expect("Could not serialize high scores.");
+    // losing the score is a shame, crashing at the end of the run would be worse
+    if let Err(error) = fs::write(HIGH_SCORE_FILE_PATH, data){
+        world.write_resource::<GameLog>().log(format!("Could not save your high score: {}", error));
+    }
+}
@@ -1,6 +1,6 @@
 use specs::prelude::*;
 
-use crate::{GameLog, WantsToPickUpItem, Position, InBackpack, Name, WantsToUseItem, CombatStats, ProvidesHealing, WantsToDropItem, InflictsDamage, Map, SuffersDamage, Consumable, AreaOfEffect, CausesConfusion, IsConfused, GivesMovementSpeed, HasMovementSpeedModifier, Equippable, Equipped, WantsToUnequipItem};
+use crate::{GameLog, LogEntry, WantsToPickUpItem, Position, InBackpack, Name, WantsToUseItem, CombatStats, WantsToDropItem, Map, Consumable, AreaOfEffect, Equippable, Equipped, WantsToUnequipItem, Gold, Wallet, WantsToThrowItem, Potion, MeleePowerBonus, MeleeDamageType, DamageType, Charges, RechargesOverTime, RunState, EffectQueue, EffectType, Targets, aimed_targets};
 
 pub struct ItemCollectionSystem{ }
 
@@ -11,18 +11,37 @@ impl<'a> System<'a> for ItemCollectionSystem{
                         WriteStorage<'a, Position>,
                         ReadStorage<'a, Name>,
                         WriteStorage<'a, InBackpack>,
+                        Entities<'a>,
+                        ReadStorage<'a, Gold>,
+                        WriteStorage<'a, Wallet>,
                     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_to_pickup_item, mut position, names, mut backpack) = data;
+        let (player_entity, mut gamelog, mut wants_to_pickup_item, mut position, names, mut backpack, entities, gold, mut wallets) = data;
 
         for pick_up_item in wants_to_pickup_item.join(){
             position.remove(pick_up_item.item);
+
+            // gold goes straight into the wallet instead of taking up a backpack slot
+            if let Some(gold_pile) = gold.get(pick_up_item.item){
+                if let Some(wallet) = wallets.get_mut(pick_up_item.collected_by){
+                    wallet.gold += gold_pile.amount;
+                }
+
+                if pick_up_item.collected_by == *player_entity{
+                    gamelog.add(LogEntry::new().text("You picked up ").colour(rltk::RGB::named(rltk::GOLD), gold_pile.amount).text(" gold."));
+                }
+
+                entities.delete(pick_up_item.item)
+                    .expect("Could not delete picked up gold.");
+                continue;
+            }
+
             backpack.insert(pick_up_item.item, InBackpack { owner: pick_up_item.collected_by })
                 .expect("Unable to insert item into backpack...");
 
             if pick_up_item.collected_by == *player_entity{
-                gamelog.entries.push(format!("You picked up {}.", names.get(pick_up_item.item).unwrap().name));
+                gamelog.add(LogEntry::new().text("You picked up ").name(&names.get(pick_up_item.item).unwrap().name).text("."));
             }
         }
 
@@ -30,6 +49,82 @@ impl<'a> System<'a> for ItemCollectionSystem{
     }
 }
 
+pub const THROW_RANGE: i32 = 6;
+
+pub struct ItemThrowSystem{ }
+
+impl<'a> System<'a> for ItemThrowSystem{
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToThrowItem>,
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Potion>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        WriteStorage<'a, AreaOfEffect>,
+                        WriteStorage<'a, Consumable>,
+                        WriteStorage<'a, WantsToUseItem>,
+                        WriteExpect<'a, EffectQueue>,
+                        ReadStorage<'a, MeleeDamageType>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut gamelog, map, entities, mut wants_to_throw_item, mut backpack, mut positions, names,
+            combat_stats, potions, melee_power_bonus, mut area_of_effect, mut consumables, mut wants_to_use_item, mut effect_queue, melee_damage_types) = data;
+
+        for (thrower, throw) in (&entities, &wants_to_throw_item).join(){
+            let start = match positions.get(thrower){
+                Some(pos) => rltk::Point::new(pos.x, pos.y),
+                None => continue,
+            };
+            let item_name = names.get(throw.item).map(|name| name.name.clone()).unwrap_or_default();
+            backpack.remove(throw.item);
+
+            // the item flies until it hits a creature or would go into a wall
+            let landing = map.projectile_path(start, throw.target, |idx| map.tile_content[idx].iter().any(|entity| combat_stats.get(*entity).is_some()))
+                .last()
+                .copied()
+                .unwrap_or(start);
+            let landing_idx = map.xy_idx(landing.x, landing.y);
+            let hit = map.tile_content[landing_idx].iter()
+                .find(|entity| **entity != thrower && combat_stats.get(**entity).is_some())
+                .copied();
+
+            // potions shatter and are used up all at once on everything around where they land
+            if potions.get(throw.item).is_some(){
+                area_of_effect.insert(throw.item, AreaOfEffect{ radius: 1 })
+                    .expect("Could not splash thrown potion.");
+                consumables.insert(throw.item, Consumable{ charges: 1 })
+                    .expect("Could not use up thrown potion.");
+                wants_to_use_item.insert(thrower, WantsToUseItem{ item: throw.item, target: Some(landing) })
+                    .expect("Could not use thrown potion.");
+
+                if thrower == *player_entity{
+                    gamelog.add(LogEntry::new().text("The ").name(&item_name).text(" shatters!"));
+                }
+                continue;
+            }
+
+            // weapons hurt whoever they hit, thrown blades land point first
+            if let (Some(weapon), Some(target)) = (melee_power_bonus.get(throw.item), hit){
+                let damage_type = melee_damage_types.get(throw.item).map(|melee_type| melee_type.damage_type).unwrap_or(DamageType::Physical);
+                effect_queue.add(Some(thrower), Some(throw.item), EffectType::Damage{ amount: weapon.power * 2, damage_type }, Targets::Entity{ target });
+            } else if thrower == *player_entity{
+                gamelog.add(LogEntry::new().text("You throw the ").name(&item_name).text("."));
+            }
+
+            positions.insert(throw.item, Position{ x: landing.x, y: landing.y })
+                .expect("Could not land thrown item.");
+        }
+
+        wants_to_throw_item.clear();
+    }
+}
+
 pub struct ItemUseSystem{ }
 
 impl<'a> System<'a> for ItemUseSystem{
@@ -37,21 +132,15 @@ impl<'a> System<'a> for ItemUseSystem{
                         Entities<'a>,
                         ReadStorage<'a, Name>,
                         WriteStorage<'a, WantsToUseItem>,
-                        WriteStorage<'a, CombatStats>,
+                        ReadStorage<'a, CombatStats>,
                         ReadExpect<'a, Entity>,
-                        ReadStorage<'a, ProvidesHealing>,
-                        ReadStorage<'a, InflictsDamage>,
                         ReadExpect<'a, Map>,
-                        WriteStorage<'a, SuffersDamage>,
-                        WriteStorage<'a, Consumable>,
+                        ReadStorage<'a, Position>,
                         ReadStorage<'a, AreaOfEffect>,
-                        ReadStorage<'a, CausesConfusion>,
-                        WriteStorage<'a, IsConfused>,
-                        ReadStorage<'a, GivesMovementSpeed>,
-                        WriteStorage<'a, HasMovementSpeedModifier>,
                         ReadStorage<'a, Equippable>,
                         WriteStorage<'a, Equipped>,
                         WriteStorage<'a, InBackpack>,
+                        WriteExpect<'a, EffectQueue>,
                      );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -59,121 +148,29 @@ impl<'a> System<'a> for ItemUseSystem{
             mut gamelog,
             entities, names,
             mut wants_to_use_item,
-            mut combat_stats,
+            combat_stats,
             player_entity,
-            provides_healing,
-            inflicts_damage,
             map,
-            mut suffers_damage,
-            mut consumables,
+            positions,
             area_of_effect,
-            causes_confusion,
-            mut is_confused,
-            gives_movement_speed,
-            mut has_movement_speed_modifier,
             equippable,
             mut equipped,
             mut backpack,
+            mut effect_queue,
         ) = data;
 
         for (entity, use_item) in (&entities, &wants_to_use_item).join(){
-            let mut used_item = false;
-
-            // find targets
-            let mut targets: Vec<Entity> = Vec::new();
-            match use_item.target{
-                // self targeted effect
-                None => {
-                    targets.push(*player_entity);
-                },
-                Some(target) => {
-                    match area_of_effect.get(use_item.item){
-                        // single target effect
-                        None => {   
-                            let map_idx = map.xy_idx(target.x, target.y);
-                            targets.extend(map.tile_content[map_idx].iter());
-                        },
-                        Some(aoe) =>{
-                            let effect_radius = rltk::field_of_view(target, aoe.radius, &*map);
-                            targets = effect_radius.iter() // for each point fov
-                                .filter(|p| Map::is_idx_valid(p.x, p.y)) // filter those that are valid idx
-                                .map(|p| map.xy_idx(p.x, p.y)) // map them to 1d idx
-                                .fold(targets, |mut acc, idx|{ // add all mobs in that tile
-                                    acc.extend(map.tile_content[idx].iter());
-                                    acc
-                                });
-                        }
-                    }
-                }
-            }
-
-            if let Some(confusing_item) = causes_confusion.get(use_item.item){
-                for target in targets.iter(){
-                    is_confused.insert(*target, IsConfused { turns: confusing_item.turns })
-                        .expect("Could not add is confused component to target.");
-
-                    if entity == *player_entity{
-                        gamelog.entries.push(format!("You use {} on {} confusing them for {} turns",
-                            names.get(use_item.item).unwrap().name, names.get(*target).unwrap().name, confusing_item.turns));
-                    }
-                }
-
-                used_item = true;
-            }
-
-            if let Some(movement_speed_item) = gives_movement_speed.get(use_item.item){
-                for target in targets.iter(){
-                    has_movement_speed_modifier.insert(*target, HasMovementSpeedModifier { speed_modifier: movement_speed_item.speed_modifier, max_turns: movement_speed_item.turns, turns_used: 0 })
-                        .expect("Could not add movement speed modifier.");
-
-                    if entity == *player_entity{
-                        gamelog.entries.push(format!("You use {} to gain a movement speed of x{} for {} turns",
-                            names.get(use_item.item).unwrap().name, movement_speed_item.speed_modifier, movement_speed_item.turns));
-                    }
-                }
-
-                used_item = true;
-            }
-
-            if let Some(potion) = provides_healing.get(use_item.item){
-                for target in targets.iter(){
-                    if let Some(stats) = combat_stats.get_mut(*target){
-                        stats.hp = i32::min(stats.max_hp, stats.hp + potion.heal_amount);
-
-                        if entity == *player_entity{
-                            gamelog.entries.push(format!("You drink the {} for {} hp", names.get(use_item.item).unwrap().name, potion.heal_amount));
-                        }  
-                    }   
-                }
-
-                used_item = true;
-            }
-
-            if let Some(item) = inflicts_damage.get(use_item.item){
-                for mob in targets.iter(){
-                    SuffersDamage::new_damage(&mut suffers_damage, *mob, item.damage);
-
-                    if entity == *player_entity{
-                        gamelog.entries.push(format!("You hit {} for {} hp with {}.",
-                            names.get(*mob).unwrap().name, item.damage, names.get(use_item.item).unwrap().name));
-                    }
-                }
-
-                used_item = true;
-            }
-
             if let Some(can_equip) = equippable.get(use_item.item){
                 let target_slot = can_equip.slot;
-                let target_entity = targets[0];
 
                 // find items in existing slot
                 let mut items_to_unequip = Vec::new();
-                for (entity, equipped, name) in (&entities, &equipped, &names).join(){
-                    if equipped.owner == target_entity && equipped.slot == target_slot{
-                        items_to_unequip.push(entity);
+                for (item, equipped, name) in (&entities, &equipped, &names).join(){
+                    if equipped.owner == entity && equipped.slot == target_slot{
+                        items_to_unequip.push(item);
 
-                        if target_entity == *player_entity{
-                            gamelog.entries.push(format!("You unequip {}", name.name));
+                        if entity == *player_entity{
+                            gamelog.add(LogEntry::new().text("You unequip ").name(&name.name));
                         }
                     }
                 }
@@ -181,33 +178,84 @@ impl<'a> System<'a> for ItemUseSystem{
                 // unequip items and place in backpack
                 for item in items_to_unequip.iter(){
                     equipped.remove(*item);
-                    backpack.insert(*item, InBackpack { owner: target_entity })
+                    backpack.insert(*item, InBackpack { owner: entity })
                         .expect("Could not insert unequipped item into backpack.");
                 }
 
                 // equip new item
-                equipped.insert(use_item.item, Equipped { owner: target_entity, slot: target_slot })
+                equipped.insert(use_item.item, Equipped { owner: entity, slot: target_slot })
                     .expect("Could not equip item.");
                 backpack.remove(use_item.item);
-                if target_entity == *player_entity{
-                    gamelog.entries.push(format!("You equip {}", names.get(use_item.item).unwrap().name))
+                if entity == *player_entity{
+                    gamelog.add(LogEntry::new().text("You equip ").name(&names.get(use_item.item).unwrap().name))
                 }
+                continue;
+            }
+
+            // anything aimed flies in a straight line and goes off at the first creature or wall in the way
+            let targets = match use_item.target{
+                // self targeted effect
+                None => Targets::Entity{ target: entity },
+                Some(target) => {
+                    let aimed_at = match positions.get(entity){
+                        None => target,
+                        Some(pos) => {
+                            let start = rltk::Point::new(pos.x, pos.y);
+                            map.projectile_path(start, target, |idx| map.tile_content[idx].iter().any(|e| combat_stats.get(*e).is_some()))
+                                .last()
+                                .copied()
+                                .unwrap_or(start)
+                        },
+                    };
+                    aimed_targets(&map, aimed_at, area_of_effect.get(use_item.item))
+                },
+            };
+
+            effect_queue.add(Some(entity), Some(use_item.item), EffectType::ItemUse{ item: use_item.item }, targets);
+        }
 
+        wants_to_use_item.clear();
+    }
+}
+
+pub struct RechargeSystem{ }
+
+impl<'a> System<'a> for RechargeSystem{
+    type SystemData = ( ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, InBackpack>,
+                        WriteStorage<'a, Charges>,
+                        WriteStorage<'a, RechargesOverTime>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (run_state, player_entity, mut gamelog, entities, names, backpack, mut charges, mut recharges_over_time) = data;
+
+        // staves regain their charges once per turn
+        if *run_state != RunState::MonsterTurn{
+            return;
+        }
+
+        for (item, item_charges, recharge) in (&entities, &mut charges, &mut recharges_over_time).join(){
+            if item_charges.current >= item_charges.max{
+                recharge.timer = 0;
+                continue;
             }
 
-            if let Some(consumable_item) = consumables.get_mut(use_item.item){
-                if used_item{
-                    consumable_item.charges -= 1;
-    
-                    if consumable_item.charges <= 0{
-                        entities.delete(use_item.item)
-                            .expect("Could not delete consumable entity.");
+            recharge.timer += 1;
+            if recharge.timer >= recharge.turns{
+                recharge.timer = 0;
+                item_charges.current += 1;
+                if backpack.get(item).map(|pack| pack.owner) == Some(*player_entity){
+                    if let Some(name) = names.get(item){
+                        gamelog.add(LogEntry::new().text("Your ").name(&name.name).text(" hums as a charge returns."));
                     }
                 }
             }
         }
-
-        wants_to_use_item.clear();
     }
 }
 
@@ -236,7 +284,7 @@ impl<'a> System<'a> for ItemDropSystem{
             in_backpack.remove(item.item);
 
             if entity == *player_entity{
-                gamelog.entries.push(format!("You drop a {}", names.get(item.item).unwrap().name));
+                gamelog.add(LogEntry::new().text("You drop a ").name(&names.get(item.item).unwrap().name));
             }
 
         }
@@ -276,7 +324,7 @@ impl<'a> System<'a> for ItemUnequipSystem{
                 .expect("Could not unequip item");
 
             if entity == *player_entity{
-                gamelog.entries.push(format!("You unequip the {}", names.get(wants_to_unequip.item).unwrap().name));
+                gamelog.add(LogEntry::new().text("You unequip the ").name(&names.get(wants_to_unequip.item).unwrap().name));
             }
 
         }
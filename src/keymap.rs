@@ -0,0 +1,311 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use rltk::VirtualKeyCode;
+use serde::{Serialize, Deserialize};
+
+pub const KEYMAP_FILE_PATH: &str = "./keymap.json";
+
+/// Everything a key can be bound to
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action{
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    PickUp,
+    Inventory,
+    Drop,
+    Throw,
+    CastSpell,
+    Unequip,
+    Descend,
+    Ascend,
+    Search,
+    CloseDoor,
+    Look,
+    AutoExplore,
+    TravelToStairs,
+    MessageLog,
+    Help,
+    MainMenu,
+    SaveAndQuit,
+    MenuUp,
+    MenuDown,
+    MenuNext,
+    MenuSelect,
+    MenuCancel,
+}
+
+impl Action{
+    /// Every action in the order the help screen lists them
+    pub const ALL: [Action; 31] = [
+        Action::MoveNorth, Action::MoveSouth, Action::MoveWest, Action::MoveEast,
+        Action::MoveNorthWest, Action::MoveNorthEast, Action::MoveSouthWest, Action::MoveSouthEast,
+        Action::Wait, Action::PickUp, Action::Inventory, Action::Drop, Action::Throw, Action::CastSpell, Action::Unequip,
+        Action::Descend, Action::Ascend, Action::Search, Action::CloseDoor,
+        Action::Look, Action::AutoExplore, Action::TravelToStairs, Action::MessageLog, Action::Help, Action::MainMenu, Action::SaveAndQuit,
+        Action::MenuUp, Action::MenuDown, Action::MenuNext, Action::MenuSelect, Action::MenuCancel,
+    ];
+
+    pub fn is_menu_action(&self) -> bool{
+        matches!(self, Action::MenuUp | Action::MenuDown | Action::MenuNext | Action::MenuSelect | Action::MenuCancel)
+    }
+
+    pub fn description(&self) -> &'static str{
+        match self{
+            Action::MoveNorth => "Move north",
+            Action::MoveSouth => "Move south",
+            Action::MoveWest => "Move west",
+            Action::MoveEast => "Move east",
+            Action::MoveNorthWest => "Move north west",
+            Action::MoveNorthEast => "Move north east",
+            Action::MoveSouthWest => "Move south west",
+            Action::MoveSouthEast => "Move south east",
+            Action::Wait => "Wait a turn",
+            Action::PickUp => "Pick up",
+            Action::Inventory => "Inventory",
+            Action::Drop => "Drop item",
+            Action::Throw => "Throw item",
+            Action::CastSpell => "Cast a spell",
+            Action::Unequip => "Unequip item",
+            Action::Descend => "Go down stairs",
+            Action::Ascend => "Go up stairs",
+            Action::Search => "Search for traps",
+            Action::CloseDoor => "Close a door",
+            Action::Look => "Look around",
+            Action::AutoExplore => "Explore",
+            Action::TravelToStairs => "Travel to stairs",
+            Action::MessageLog => "Message log",
+            Action::Help => "This help",
+            Action::MainMenu => "Main menu",
+            Action::SaveAndQuit => "Save and quit",
+            Action::MenuUp => "Menu up",
+            Action::MenuDown => "Menu down",
+            Action::MenuNext => "Menu next tab",
+            Action::MenuSelect => "Menu select",
+            Action::MenuCancel => "Menu back",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Preset{
+    Wasd,
+    ViKeys,
+    Numpad,
+}
+
+/// What the keymap file holds: a preset to start from and any bindings that replace the preset's
+#[derive(Serialize, Deserialize)]
+struct KeymapFile{
+    preset: Preset,
+    #[serde(default)]
+    overrides: HashMap<Action, Vec<String>>,
+}
+
+/// Key names the keymap file can use, spelled the way rltk names them
+const KEY_NAMES: &[(&str, VirtualKeyCode)] = &[
+    ("A", VirtualKeyCode::A), ("B", VirtualKeyCode::B), ("C", VirtualKeyCode::C), ("D", VirtualKeyCode::D),
+    ("E", VirtualKeyCode::E), ("F", VirtualKeyCode::F), ("G", VirtualKeyCode::G), ("H", VirtualKeyCode::H),
+    ("I", VirtualKeyCode::I), ("J", VirtualKeyCode::J), ("K", VirtualKeyCode::K), ("L", VirtualKeyCode::L),
+    ("M", VirtualKeyCode::M), ("N", VirtualKeyCode::N), ("O", VirtualKeyCode::O), ("P", VirtualKeyCode::P),
+    ("Q", VirtualKeyCode::Q), ("R", VirtualKeyCode::R), ("S", VirtualKeyCode::S), ("T", VirtualKeyCode::T),
+    ("U", VirtualKeyCode::U), ("V", VirtualKeyCode::V), ("W", VirtualKeyCode::W), ("X", VirtualKeyCode::X),
+    ("Y", VirtualKeyCode::Y), ("Z", VirtualKeyCode::Z),
+    ("Key0", VirtualKeyCode::Key0), ("Key1", VirtualKeyCode::Key1), ("Key2", VirtualKeyCode::Key2),
+    ("Key3", VirtualKeyCode::Key3), ("Key4", VirtualKeyCode::Key4), ("Key5", VirtualKeyCode::Key5),
+    ("Key6", VirtualKeyCode::Key6), ("Key7", VirtualKeyCode::Key7), ("Key8", VirtualKeyCode::Key8),
+    ("Key9", VirtualKeyCode::Key9),
+    ("Numpad0", VirtualKeyCode::Numpad0), ("Numpad1", VirtualKeyCode::Numpad1), ("Numpad2", VirtualKeyCode::Numpad2),
+    ("Numpad3", VirtualKeyCode::Numpad3), ("Numpad4", VirtualKeyCode::Numpad4), ("Numpad5", VirtualKeyCode::Numpad5),
+    ("Numpad6", VirtualKeyCode::Numpad6), ("Numpad7", VirtualKeyCode::Numpad7), ("Numpad8", VirtualKeyCode::Numpad8),
+    ("Numpad9", VirtualKeyCode::Numpad9), ("NumpadDecimal", VirtualKeyCode::NumpadDecimal),
+    ("NumpadEnter", VirtualKeyCode::NumpadEnter), ("NumpadAdd", VirtualKeyCode::NumpadAdd),
+    ("NumpadSubtract", VirtualKeyCode::NumpadSubtract),
+    ("Up", VirtualKeyCode::Up), ("Down", VirtualKeyCode::Down), ("Left", VirtualKeyCode::Left), ("Right", VirtualKeyCode::Right),
+    ("Home", VirtualKeyCode::Home), ("End", VirtualKeyCode::End), ("PageUp", VirtualKeyCode::PageUp), ("PageDown", VirtualKeyCode::PageDown),
+    ("Escape", VirtualKeyCode::Escape), ("Return", VirtualKeyCode::Return), ("Space", VirtualKeyCode::Space),
+    ("Tab", VirtualKeyCode::Tab), ("Back", VirtualKeyCode::Back),
+    ("Period", VirtualKeyCode::Period), ("Comma", VirtualKeyCode::Comma), ("Slash", VirtualKeyCode::Slash),
+    ("Semicolon", VirtualKeyCode::Semicolon), ("Minus", VirtualKeyCode::Minus), ("Equals", VirtualKeyCode::Equals),
+    ("F1", VirtualKeyCode::F1), ("F2", VirtualKeyCode::F2), ("F3", VirtualKeyCode::F3), ("F4", VirtualKeyCode::F4),
+];
+
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode>{
+    KEY_NAMES.iter().find(|(key_name, _key)| *key_name == name).map(|(_key_name, key)| *key)
+}
+
+pub fn key_name(key: VirtualKeyCode) -> &'static str{
+    KEY_NAMES.iter().find(|(_key_name, k)| *k == key).map(|(key_name, _key)| *key_name).unwrap_or("?")
+}
+
+/// Which keys trigger which actions. A key can be bound to one gameplay action and one menu action
+pub struct Keymap{
+    pub preset: Preset,
+    pub bindings: HashMap<Action, Vec<VirtualKeyCode>>,
+    /// what was wrong with the keymap file, shown in the log when the game starts
+    pub problems: Vec<String>,
+}
+
+impl Keymap{
+    pub fn preset(preset: Preset) -> Keymap{
+        use VirtualKeyCode::*;
+
+        let mut bindings: HashMap<Action, Vec<VirtualKeyCode>> = HashMap::new();
+        let mut bind = |action: Action, keys: &[VirtualKeyCode]| { bindings.insert(action, keys.to_vec()); };
+
+        // menus and the commands nobody argues about are the same everywhere
+        bind(Action::PickUp, &[G]);
+        bind(Action::Inventory, &[I]);
+        bind(Action::Drop, &[R]);
+        bind(Action::Descend, &[Period]);
+        bind(Action::Ascend, &[Comma]);
+        bind(Action::Search, &[F]);
+        bind(Action::CloseDoor, &[X]);
+        bind(Action::MessageLog, &[P]);
+        bind(Action::AutoExplore, &[O]);
+        bind(Action::Help, &[Slash, F1]);
+        bind(Action::MainMenu, &[M]);
+        bind(Action::SaveAndQuit, &[Escape]);
+        bind(Action::MenuNext, &[Tab]);
+        bind(Action::MenuSelect, &[Return]);
+        bind(Action::MenuCancel, &[Escape]);
+
+        match preset{
+            Preset::Wasd => {
+                bind(Action::MoveNorth, &[W, Up]);
+                bind(Action::MoveSouth, &[S, Down]);
+                bind(Action::MoveWest, &[A, Left]);
+                bind(Action::MoveEast, &[D, Right]);
+                bind(Action::MoveNorthWest, &[Q]);
+                bind(Action::MoveNorthEast, &[E]);
+                bind(Action::MoveSouthWest, &[Z]);
+                bind(Action::MoveSouthEast, &[C]);
+                bind(Action::Wait, &[Space]);
+                bind(Action::Unequip, &[U]);
+                bind(Action::Look, &[L]);
+                bind(Action::TravelToStairs, &[T]);
+                bind(Action::Throw, &[H]);
+                bind(Action::CastSpell, &[V]);
+                bind(Action::MenuUp, &[W, Up]);
+                bind(Action::MenuDown, &[S, Down]);
+            },
+            Preset::ViKeys => {
+                bind(Action::MoveNorth, &[K, Up]);
+                bind(Action::MoveSouth, &[J, Down]);
+                bind(Action::MoveWest, &[H, Left]);
+                bind(Action::MoveEast, &[L, Right]);
+                bind(Action::MoveNorthWest, &[Y]);
+                bind(Action::MoveNorthEast, &[U]);
+                bind(Action::MoveSouthWest, &[B]);
+                bind(Action::MoveSouthEast, &[N]);
+                bind(Action::Wait, &[Space]);
+                bind(Action::Unequip, &[T]);
+                bind(Action::Look, &[V]);
+                bind(Action::TravelToStairs, &[Semicolon]);
+                bind(Action::Throw, &[Z]);
+                bind(Action::CastSpell, &[C]);
+                bind(Action::MenuUp, &[K, Up]);
+                bind(Action::MenuDown, &[J, Down]);
+            },
+            Preset::Numpad => {
+                bind(Action::MoveNorth, &[Numpad8, Up]);
+                bind(Action::MoveSouth, &[Numpad2, Down]);
+                bind(Action::MoveWest, &[Numpad4, Left]);
+                bind(Action::MoveEast, &[Numpad6, Right]);
+                bind(Action::MoveNorthWest, &[Numpad7]);
+                bind(Action::MoveNorthEast, &[Numpad9]);
+                bind(Action::MoveSouthWest, &[Numpad1]);
+                bind(Action::MoveSouthEast, &[Numpad3]);
+                bind(Action::Wait, &[Numpad5, Space]);
+                bind(Action::Unequip, &[U]);
+                bind(Action::Look, &[L]);
+                bind(Action::TravelToStairs, &[T]);
+                bind(Action::Throw, &[H]);
+                bind(Action::CastSpell, &[V]);
+                bind(Action::MenuUp, &[Numpad8, Up]);
+                bind(Action::MenuDown, &[Numpad2, Down]);
+            },
+        }
+
+        Keymap{ preset, bindings, problems: Vec::new() }
+    }
+
+    /// Read the keymap file. If there isn't one a default is written so players have something to edit
+    pub fn load() -> Keymap{
+        if !Path::new(KEYMAP_FILE_PATH).exists(){
+            let default_file = KeymapFile{ preset: Preset::Wasd, overrides: HashMap::new() };
+            let data = serde_json::to_string_pretty(&default_file)
+                .expect("Could not serialize keymap.");
+            // not being able to write the file just means the defaults aren't saved
+            let _ = fs::write(KEYMAP_FILE_PATH, data);
+            return Keymap::preset(Preset::Wasd);
+        }
+
+        let mut problems: Vec<String> = Vec::new();
+        let keymap_file: KeymapFile = match fs::read_to_string(KEYMAP_FILE_PATH)
+            .map_err(|error| error.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|error| error.to_string())){
+            Ok(keymap_file) => keymap_file,
+            Err(error) => {
+                problems.push(format!("Could not read {}, using WASD keys: {}", KEYMAP_FILE_PATH, error));
+                KeymapFile{ preset: Preset::Wasd, overrides: HashMap::new() }
+            },
+        };
+
+        // overrides go in a fixed order so two of them fighting over a key always end the same way
+        let mut keymap = Keymap::preset(keymap_file.preset);
+        for action in Action::ALL.iter(){
+            if let Some(key_names) = keymap_file.overrides.get(action){
+                let mut keys: Vec<VirtualKeyCode> = Vec::new();
+                for name in key_names{
+                    match key_from_name(name){
+                        Some(key) => keys.push(key),
+                        None => problems.push(format!("Unknown key \"{}\" for {} in {}", name, action.description(), KEYMAP_FILE_PATH)),
+                    }
+                }
+
+                // an action left without keys could lock the player in, so it keeps the preset ones
+                if keys.is_empty(){
+                    problems.push(format!("No usable keys for {}, keeping {}", action.description(), keymap.key_names(*action)));
+                    continue;
+                }
+
+                // an overridden key stops doing whatever the preset had it do
+                for (other, bound) in keymap.bindings.iter_mut(){
+                    if other != action && other.is_menu_action() == action.is_menu_action(){
+                        bound.retain(|key| !keys.contains(key));
+                    }
+                }
+                keymap.bindings.insert(*action, keys);
+            }
+        }
+        keymap.problems = problems;
+
+        keymap
+    }
+
+    pub fn is_pressed(&self, key: VirtualKeyCode, action: Action) -> bool{
+        self.bindings.get(&action).is_some_and(|keys| keys.contains(&key))
+    }
+
+    /// The gameplay action a key is bound to, ignoring menu bindings
+    pub fn game_action(&self, key: VirtualKeyCode) -> Option<Action>{
+        Action::ALL.iter()
+            .filter(|action| !action.is_menu_action())
+            .find(|action| self.is_pressed(key, **action))
+            .copied()
+    }
+
+    /// The keys bound to an action, for showing on screen
+    pub fn key_names(&self, action: Action) -> String{
+        self.bindings.get(&action)
+            .map(|keys| keys.iter().map(|key| key_name(*key)).collect::<Vec<_>>().join(", "))
+            .unwrap_or_default()
+    }
+}
@@ -36,6 +36,32 @@ mod save_system;
 pub use crate::save_system::*;
 mod random_table;
 pub use crate::random_table::*;
+mod shop_system;
+pub use crate::shop_system::*;
+mod trigger_system;
+pub use crate::trigger_system::*;
+mod hazard_system;
+pub use crate::hazard_system::*;
+mod camera;
+pub use crate::camera::*;
+mod prefab_builder;
+pub use crate::prefab_builder::*;
+mod theme;
+pub use crate::theme::*;
+mod cave_builder;
+pub use crate::cave_builder::*;
+mod run_stats;
+pub use crate::run_stats::*;
+mod high_scores;
+pub use crate::high_scores::*;
+mod keymap;
+pub use crate::keymap::*;
+mod auto_move;
+pub use crate::auto_move::*;
+mod spell_system;
+pub use crate::spell_system::*;
+mod effects;
+pub use crate::effects::*;
 
 
 #[derive(PartialEq, Clone, Copy)]
@@ -46,12 +72,22 @@ pub enum RunState{
     MonsterTurn, // run and update monsters
     InInventory,
     ShowDropItem,
-    ShowTargetting{ range: i32, item: Entity, },
+    ShowThrowItem,
+    ShowCastSpell,
+    ShowThrowTargetting{ item: Entity, cursor: Option<Point> },
+    ShowTargetting{ range: i32, item: Entity, cursor: Option<Point> },
     MainMenu{ menu_selection: gui::MainMenuSelection },
     SaveGame,
     DescendFloor,
+    AscendFloor,
     ShowUnequipItem,
     GameOver,
+    Victory,
+    ShowHighScores,
+    ShowLog{ scroll: i32 },
+    ShowHelp,
+    Looking{ index: usize },
+    ShowShop{ vendor: Entity, mode: gui::ShopMode },
 }
 
 pub struct State {
@@ -74,6 +110,14 @@ impl State{
         let mut map_indexing_system = MapIndexingSystem{ };
         map_indexing_system.run_now(&self.world);
 
+        // traps and other entry triggers
+        let mut trigger_system = TriggerSystem{ };
+        trigger_system.run_now(&self.world);
+
+        // fire, gas and smoke
+        let mut hazard_system = HazardSystem{ };
+        hazard_system.run_now(&self.world);
+
         // melee combat system
         let mut melee_combat_system = MeleeCombatSystem{ };
         melee_combat_system.run_now(&self.world);
@@ -86,27 +130,47 @@ impl State{
         let mut item_collection_system = ItemCollectionSystem{ };
         item_collection_system.run_now(&self.world);
 
+        let mut item_throw_system = ItemThrowSystem{ };
+        item_throw_system.run_now(&self.world);
+
+        let mut spell_cast_system = SpellCastSystem{ };
+        spell_cast_system.run_now(&self.world);
+
         let mut item_use_system = ItemUseSystem{ };
         item_use_system.run_now(&self.world);
 
+        // everything items, spells, traps and abilities do
+        run_effects_queue(&mut self.world);
+
         let mut item_drop_system = ItemDropSystem{ };
         item_drop_system.run_now(&self.world);
 
         let mut movement_speed_modifier = MovementSpeedModifier{ };
         movement_speed_modifier.run_now(&self.world);
 
+        let mut recharge_system = RechargeSystem{ };
+        recharge_system.run_now(&self.world);
+
+        let mut mana_regen_system = ManaRegenSystem{ };
+        mana_regen_system.run_now(&self.world);
+
         let mut item_unequip_system = ItemUnequipSystem{ };
         item_unequip_system.run_now(&self.world);
 
+        let mut shop_system = ShopSystem{ };
+        shop_system.run_now(&self.world);
+
         self.world.maintain();
     }
 
-    pub fn entities_to_remove_on_depth_change(&mut self) -> Vec<Entity>{
+    /// Everything that stays on the level being left, which is anything the player doesn't own
+    pub fn entities_left_on_depth_change(&mut self) -> Vec<Entity>{
         let entities = self.world.entities();
         let players = self.world.read_storage::<Player>();
         let backpacks = self.world.read_storage::<InBackpack>();
         let player_entity = self.world.fetch::<Entity>();
         let equipped = self.world.read_storage::<Equipped>();
+        let known_spells = self.world.read_storage::<KnownSpell>();
     
         let mut entities_to_delete = Vec::new();
         for entity in entities.join(){
@@ -127,6 +191,12 @@ impl State{
                     should_delete = false;
                 }
             }
+
+            if let Some(known) = known_spells.get(entity){
+                if known.owner == *player_entity{
+                    should_delete = false;
+                }
+            }
     
             if should_delete{
                 entities_to_delete.push(entity);
@@ -136,28 +206,68 @@ impl State{
         entities_to_delete
     }
 
-    pub fn goto_next_level(&mut self){
-        let entities_to_delete = self.entities_to_remove_on_depth_change();
-
-        for entity in entities_to_delete{
-            self.world.delete_entity(entity)
-                .expect("Could not delete entity on level change.");
-        }
+    /// Put the current level away and move the player to new_depth, keeping what they carry.
+    /// A level visited before comes back as it was left, otherwise a fresh one is built.
+    /// The player arrives on the arrival tile if the new level has one, otherwise on its starting position
+    fn change_level(&mut self, new_depth: i32, arrival: TileType){
+        let old_map = (*self.world.fetch::<Map>()).clone();
 
-        let new_level_map: Map;
-        let current_depth: i32;
+        // whatever is on the level waits there without a position until the player comes back
+        let left_behind = self.entities_left_on_depth_change();
         {
-            let mut map = self.world.write_resource::<Map>();
-            current_depth = map.depth;
-            *map = Map::map_with_rooms_and_corridors(current_depth+1);
-            new_level_map = map.clone();
+            let mut positions = self.world.write_storage::<Position>();
+            let mut other_level_positions = self.world.write_storage::<OtherLevelPosition>();
+            for entity in left_behind{
+                if let Some(pos) = positions.remove(entity){
+                    other_level_positions.insert(entity, OtherLevelPosition{ x: pos.x, y: pos.y, depth: old_map.depth })
+                        .expect("Could not leave entity on its level.");
+                }
+            }
         }
 
-        for room in new_level_map.rooms.iter().skip(1){
-            spawn_entities_in_room(&mut self.world, room, current_depth+1);
-        }
+        let visited_map = {
+            let mut visited_levels = self.world.write_resource::<VisitedLevels>();
+            let visited_map = visited_levels.maps.remove(&new_depth);
+            visited_levels.maps.insert(old_map.depth, old_map);
+            visited_map
+        };
+
+        let new_level_map = match visited_map{
+            Some(mut map) => {
+                map.load_from_save();
+                *self.world.write_resource::<Map>() = map.clone();
+
+                let entities = self.world.entities();
+                let mut positions = self.world.write_storage::<Position>();
+                let mut other_level_positions = self.world.write_storage::<OtherLevelPosition>();
+                let returning: Vec<(Entity, i32, i32)> = (&entities, &other_level_positions).join()
+                    .filter(|(_entity, other)| other.depth == new_depth)
+                    .map(|(entity, other)| (entity, other.x, other.y))
+                    .collect();
+                for (entity, x, y) in returning{
+                    other_level_positions.remove(entity);
+                    positions.insert(entity, Position{ x, y })
+                        .expect("Could not bring entity back to its level.");
+                }
+
+                // monsters look around again on the level they are back on
+                for fov in (&mut self.world.write_storage::<FOV>()).join(){
+                    fov.needs_update = true;
+                }
+                map
+            },
+            None => {
+                let map = Map::new_level(new_depth);
+                *self.world.write_resource::<Map>() = map.clone();
+                populate_level(&mut self.world, &map);
+                map
+            },
+        };
 
-        let (player_x, player_y) = new_level_map.rooms[0].center();
+        let (player_x, player_y) = match new_level_map.tiles.iter().position(|tile| *tile == arrival){
+            Some(idx) => (idx as i32 % new_level_map.width, idx as i32 / new_level_map.width),
+            None => new_level_map.starting_position,
+        };
         let mut player_position = self.world.write_resource::<Point>();
         *player_position = Point::new(player_x, player_y);
 
@@ -174,10 +284,19 @@ impl State{
         if let Some(player_fov) = fov.get_mut(*player_entity){
             player_fov.needs_update = true;
         }
+    }
+
+    pub fn goto_next_level(&mut self){
+        let current_depth = self.world.fetch::<Map>().depth;
+        self.change_level(current_depth+1, TileType::UpStairs);
+
+        let mut run_stats = self.world.write_resource::<RunStats>();
+        run_stats.deepest_depth = i32::max(run_stats.deepest_depth, current_depth+1);
 
         let mut gamelog = self.world.fetch_mut::<GameLog>();
-        gamelog.entries.push("You descend a level and get some health.".to_string());
+        gamelog.log("You descend a level and get some health.");
 
+        let player_entity = self.world.fetch::<Entity>();
         let mut combat_stats = self.world.write_storage::<CombatStats>();
         if let Some(player_stats) = combat_stats.get_mut(*player_entity){
             player_stats.hp = i32::max(player_stats.hp, player_stats.max_hp/2);
@@ -185,6 +304,25 @@ impl State{
 
     }
 
+    /// Climb back to the level above, as the player left it
+    pub fn goto_previous_level(&mut self){
+        let current_depth = self.world.fetch::<Map>().depth;
+        self.change_level(current_depth-1, TileType::DownStairs);
+
+        let mut gamelog = self.world.fetch_mut::<GameLog>();
+        gamelog.log("You climb back up a level.");
+    }
+
+    /// True if the player has the artifact in their backpack
+    pub fn player_has_artifact(&self) -> bool{
+        let player_entity = self.world.fetch::<Entity>();
+        let backpacks = self.world.read_storage::<InBackpack>();
+        let artifacts = self.world.read_storage::<Artifact>();
+
+        (&backpacks, &artifacts).join()
+            .any(|(backpack, _artifact)| backpack.owner == *player_entity)
+    }
+
     pub fn game_over_cleanup(&mut self){
         let mut entities: Vec<Entity> = Vec::new();
         for e in self.world.entities().join(){
@@ -199,17 +337,21 @@ impl State{
         let world_map;
         {
             let mut map_resource = self.world.write_resource::<Map>();
-            *map_resource = Map::map_with_rooms_and_corridors(1);
+            *map_resource = Map::new_level(0);
             world_map = map_resource.clone();
         }
 
-        for room in world_map.rooms.iter().skip(1){
-            spawner::spawn_entities_in_room(&mut self.world, room, 1);
-        }
+        spawner::populate_level(&mut self.world, &world_map);
 
-        let (player_x, player_y) = world_map.rooms[0].center();
+        let (player_x, player_y) = world_map.starting_position;
         let player_entity = spawner::player(&mut self.world, player_x, player_y);
 
+        let mut run_stats = self.world.write_resource::<RunStats>();
+        *run_stats = RunStats::default();
+        *self.world.write_resource::<VisitedLevels>() = VisitedLevels::default();
+        *self.world.write_resource::<AutoMove>() = AutoMove::default();
+        self.world.write_resource::<GameLog>().turn = 0;
+
         let mut player_position = self.world.write_resource::<Point>();
         *player_position = Point::new(player_x, player_y);
 
@@ -229,7 +371,7 @@ impl GameState for State {
 
         // don't do rendering if we are in main menu
         match run_state {
-            RunState::MainMenu { .. } => {},
+            RunState::MainMenu { .. } | RunState::ShowHighScores | RunState::ShowLog { .. } | RunState::ShowHelp => {},
             _ => {
                 draw_map(&self.world, context);
                 gui::draw_ui(&self.world, context);
@@ -239,13 +381,18 @@ impl GameState for State {
                 let renderables = self.world.read_storage::<Renderable>();
                 let map = self.world.fetch::<Map>();
 
-                let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+                let hidden = self.world.read_storage::<Hidden>();
+                let mut data = (&positions, &renderables, !&hidden).join()
+                    .map(|(pos, render, _hidden)| (pos, render))
+                    .collect::<Vec<_>>();
                 data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
 
                 for (pos, render) in data.iter(){
                     let idx = map.xy_idx(pos.x, pos.y);
                     if map.currently_visible_tiles[idx]{
-                        context.set(pos.x, pos.y, render.foreground, render.background, render.symbol);
+                        if let Some(screen) = map_to_screen(&self.world, Point::new(pos.x, pos.y)){
+                            context.set(screen.x, screen.y, render.foreground, render.background, render.symbol);
+                        }
                     }
                     
                 }
@@ -261,6 +408,12 @@ impl GameState for State {
                 run_state = player_input(self, context);
             },
             RunState::PlayerTurn => {
+                let turn = {
+                    let mut run_stats = self.world.write_resource::<RunStats>();
+                    run_stats.turns += 1;
+                    run_stats.turns
+                };
+                self.world.write_resource::<GameLog>().turn = turn;
                 self.run_systems();
                 run_state = RunState::MonsterTurn;
             },
@@ -279,7 +432,7 @@ impl GameState for State {
                         let ranged_items = self.world.read_storage::<Ranged>();
 
                         if let Some(ranged_item) = ranged_items.get(selected_item){
-                            run_state = RunState::ShowTargetting { range: ranged_item.range, item: selected_item }
+                            run_state = RunState::ShowTargetting { range: ranged_item.range, item: selected_item, cursor: None }
                         } else {
                             let mut wants_to_use_item = self.world.write_storage::<WantsToUseItem>();
     
@@ -309,16 +462,54 @@ impl GameState for State {
                     },
                 }
             },
-            RunState::ShowTargetting { range, item } => {
-                let (item_menu_result, selected_point) = gui::show_ranged_targeting(&mut self.world, context, range);
+            RunState::ShowCastSpell => {
+                match gui::show_cast_menu(&mut self.world, context){
+                    (ItemMenuResult::Exit, _) => run_state = RunState::AwaitingInput,
+                    (ItemMenuResult::NoResponse, _) => {},
+                    (ItemMenuResult::Selected, spell) => run_state = begin_cast(&mut self.world, spell.unwrap()),
+                }
+            },
+            RunState::ShowThrowItem => {
+                match gui::show_throw_item_menu(&mut self.world, context){
+                    (ItemMenuResult::Exit, _) => run_state = RunState::AwaitingInput,
+                    (ItemMenuResult::NoResponse, _) => {},
+                    (ItemMenuResult::Selected, item) => run_state = RunState::ShowThrowTargetting { item: item.unwrap(), cursor: None },
+                }
+            },
+            RunState::ShowThrowTargetting { item, cursor } => {
+                // only potions burst when they land
+                let radius = if self.world.read_storage::<Potion>().get(item).is_some() { 1 } else { 0 };
+                let (item_menu_result, selected_point) = gui::show_ranged_targeting(&mut self.world, context, THROW_RANGE, radius, cursor);
 
                 match item_menu_result{
                     ItemMenuResult::Exit => run_state = RunState::AwaitingInput,
-                    ItemMenuResult::NoResponse => {},
+                    ItemMenuResult::NoResponse => run_state = RunState::ShowThrowTargetting { item, cursor: selected_point },
                     ItemMenuResult::Selected => {
-                        let mut wants_to_use_item = self.world.write_storage::<WantsToUseItem>();
-                        wants_to_use_item.insert(*self.world.fetch::<Entity>(), WantsToUseItem { item: item, target: selected_point })
-                            .expect("Could not use targeted item");
+                        let mut wants_to_throw_item = self.world.write_storage::<WantsToThrowItem>();
+                        wants_to_throw_item.insert(*self.world.fetch::<Entity>(), WantsToThrowItem { item, target: selected_point.unwrap() })
+                            .expect("Could not throw item.");
+                        run_state = RunState::PlayerTurn;
+                    }
+                }
+            },
+            RunState::ShowTargetting { range, item, cursor } => {
+                let radius = self.world.read_storage::<AreaOfEffect>().get(item).map(|aoe| aoe.radius).unwrap_or(0);
+                let (item_menu_result, selected_point) = gui::show_ranged_targeting(&mut self.world, context, range, radius, cursor);
+
+                match item_menu_result{
+                    ItemMenuResult::Exit => run_state = RunState::AwaitingInput,
+                    ItemMenuResult::NoResponse => run_state = RunState::ShowTargetting { range, item, cursor: selected_point },
+                    ItemMenuResult::Selected => {
+                        // spells are paid for before they take effect
+                        if self.world.read_storage::<Spell>().get(item).is_some(){
+                            let mut wants_to_cast_spell = self.world.write_storage::<WantsToCastSpell>();
+                            wants_to_cast_spell.insert(*self.world.fetch::<Entity>(), WantsToCastSpell { spell: item, target: selected_point })
+                                .expect("Could not cast targeted spell");
+                        } else {
+                            let mut wants_to_use_item = self.world.write_storage::<WantsToUseItem>();
+                            wants_to_use_item.insert(*self.world.fetch::<Entity>(), WantsToUseItem { item: item, target: selected_point })
+                                .expect("Could not use targeted item");
+                        }
                         run_state = RunState::PlayerTurn;
 
                     }
@@ -341,6 +532,10 @@ impl GameState for State {
                                 run_state = RunState::AwaitingInput;
                                 delete_save();
                             },
+                            MainMenuSelection::HighScores =>{
+                                self.world.insert(HighScoreTable{ scores: load_high_scores() });
+                                run_state = RunState::ShowHighScores;
+                            },
                             MainMenuSelection::Quit =>{
                                 ::std::process::exit(0);
                             }
@@ -357,6 +552,17 @@ impl GameState for State {
                 self.goto_next_level();
                 run_state = RunState::PreRun;
             },
+            RunState::AscendFloor =>{
+                self.goto_previous_level();
+
+                // making it back to the town with the artifact ends the run
+                if self.world.fetch::<Map>().depth == 0 && self.player_has_artifact(){
+                    record_high_score(&self.world, true);
+                    run_state = RunState::Victory;
+                } else {
+                    run_state = RunState::PreRun;
+                }
+            },
             RunState::ShowUnequipItem =>{
                 let (item_menu_result, unequip_item_option) = gui::show_unequip_item_menu(&mut self.world, context);
 
@@ -373,7 +579,39 @@ impl GameState for State {
                 }
             },
             RunState::GameOver =>{
-                let end_screen_selection = gui::game_over(context);
+                let end_screen_selection = gui::game_over(&self.world, context);
+                match end_screen_selection{
+                    GameOverResult::NoSelection => {},
+                    GameOverResult::GoToMainMenu =>{
+                        self.game_over_cleanup();
+                        run_state = RunState::MainMenu { menu_selection: MainMenuSelection::NewGame };
+                    },
+                }
+            },
+            RunState::Looking { index } =>{
+                match gui::show_look(&self.world, context, index){
+                    (ItemMenuResult::Exit, _) => run_state = RunState::AwaitingInput,
+                    (_, next_index) => run_state = RunState::Looking { index: next_index },
+                }
+            },
+            RunState::ShowLog { scroll } =>{
+                match gui::show_log(&self.world, context, scroll){
+                    (ItemMenuResult::Exit, _) => run_state = RunState::AwaitingInput,
+                    (_, next_scroll) => run_state = RunState::ShowLog { scroll: next_scroll },
+                }
+            },
+            RunState::ShowHelp =>{
+                if gui::show_help(&self.world, context) == ItemMenuResult::Exit{
+                    run_state = RunState::AwaitingInput;
+                }
+            },
+            RunState::ShowHighScores =>{
+                if gui::show_high_scores(&self.world, context) == ItemMenuResult::Exit{
+                    run_state = RunState::MainMenu { menu_selection: MainMenuSelection::HighScores };
+                }
+            },
+            RunState::Victory =>{
+                let end_screen_selection = gui::victory(&self.world, context);
                 match end_screen_selection{
                     GameOverResult::NoSelection => {},
                     GameOverResult::GoToMainMenu =>{
@@ -381,6 +619,33 @@ impl GameState for State {
                         run_state = RunState::MainMenu { menu_selection: MainMenuSelection::NewGame };
                     },
                 }
+            },
+            RunState::ShowShop { vendor, mode } =>{
+                let (item_menu_result, selected_item, next_mode) = gui::show_shop(&mut self.world, context, vendor, mode);
+
+                match item_menu_result{
+                    ItemMenuResult::NoResponse => run_state = RunState::ShowShop { vendor, mode: next_mode },
+                    ItemMenuResult::Exit => run_state = RunState::AwaitingInput,
+                    ItemMenuResult::Selected =>{
+                        let item = selected_item.unwrap();
+                        let player_entity = *self.world.fetch::<Entity>();
+
+                        match mode{
+                            ShopMode::Buy =>{
+                                let price = item_price(&self.world, item);
+                                self.world.write_storage::<WantsToBuyItem>().insert(player_entity, WantsToBuyItem { item, vendor, price })
+                                    .expect("Could not insert intent: wants to buy item");
+                            },
+                            ShopMode::Sell =>{
+                                let price = item_sell_price(&self.world, item);
+                                self.world.write_storage::<WantsToSellItem>().insert(player_entity, WantsToSellItem { item, vendor, price })
+                                    .expect("Could not insert intent: wants to sell item");
+                            },
+                        }
+
+                        run_state = RunState::PlayerTurn;
+                    }
+                }
             }
         }
 
@@ -409,6 +674,7 @@ fn main() -> rltk::BError {
 
     // register components to game, attributes an entity can have
     game_state.world.register::<Position>();
+    game_state.world.register::<OtherLevelPosition>();
     game_state.world.register::<Renderable>();
     // game_state.world.register::<UpMover>();
     game_state.world.register::<Player>();
@@ -440,14 +706,49 @@ fn main() -> rltk::BError {
     game_state.world.register::<MeleePowerBonus>();
     game_state.world.register::<DefenseBonus>();
     game_state.world.register::<WantsToUnequipItem>();
+    game_state.world.register::<Gold>();
+    game_state.world.register::<Wallet>();
+    game_state.world.register::<Shopkeeper>();
+    game_state.world.register::<WantsToBuyItem>();
+    game_state.world.register::<WantsToSellItem>();
+    game_state.world.register::<LootTable>();
+    game_state.world.register::<Hidden>();
+    game_state.world.register::<EntryTrigger>();
+    game_state.world.register::<EntityMoved>();
+    game_state.world.register::<Teleports>();
+    game_state.world.register::<SoundsAlarm>();
+    game_state.world.register::<Alerted>();
+    game_state.world.register::<Key>();
+    game_state.world.register::<StartsFire>();
+    game_state.world.register::<CreatesGas>();
+    game_state.world.register::<Burning>();
+    game_state.world.register::<Artifact>();
+    game_state.world.register::<UsesAbility>();
+    game_state.world.register::<SummonsMinions>();
+    game_state.world.register::<Potion>();
+    game_state.world.register::<WantsToThrowItem>();
+    game_state.world.register::<Charges>();
+    game_state.world.register::<RechargesOverTime>();
+    game_state.world.register::<RechargesItems>();
+    game_state.world.register::<Mana>();
+    game_state.world.register::<Spell>();
+    game_state.world.register::<KnownSpell>();
+    game_state.world.register::<TeachesSpell>();
+    game_state.world.register::<WantsToCastSpell>();
+    game_state.world.register::<MeleeDamageType>();
+    game_state.world.register::<Resistant>();
+    game_state.world.register::<Vulnerable>();
+    game_state.world.register::<LastDamagedBy>();
+    game_state.world.register::<Thorns>();
+    game_state.world.register::<Grudge>();
 
     game_state.world.insert(SimpleMarkerAllocator::<SerializeMe>::new());
     
     
-    let map: Map = Map::map_with_rooms_and_corridors(1);
+    let map: Map = Map::new_level(0);
     
     // get valid x, y for player
-    let (player_x, player_y) = map.rooms[0].center();
+    let (player_x, player_y) = map.starting_position;
     
     // create entities, something in the world with components
     // this is player entity
@@ -455,9 +756,7 @@ fn main() -> rltk::BError {
 
     game_state.world.insert(rltk::RandomNumberGenerator::new());
     
-    for room in map.rooms.iter().skip(1){
-        spawner::spawn_entities_in_room(&mut game_state.world, room, 1);
-    }
+    spawner::populate_level(&mut game_state.world, &map);
     
     // make map resource availale to world
     game_state.world.insert(map);
@@ -466,7 +765,20 @@ fn main() -> rltk::BError {
     game_state.world.insert(player_entity);
     // insert run state as resource
     game_state.world.insert(RunState::PreRun);
-    game_state.world.insert(GameLog{ entries: vec!["Welcome!".to_string()]});
+    game_state.world.insert(RunStats::default());
+    game_state.world.insert(VisitedLevels::default());
+    let keymap = Keymap::load();
+    let keymap_problems = keymap.problems.clone();
+    game_state.world.insert(keymap);
+    game_state.world.insert(AutoMove::default());
+    game_state.world.insert(EffectQueue::default());
+    game_state.world.insert(HighScoreTable::default());
+    let mut gamelog = GameLog::default();
+    gamelog.log("Welcome!");
+    for problem in keymap_problems{
+        gamelog.log(problem);
+    }
+    game_state.world.insert(gamelog);
     
     rltk::main_loop(context, game_state)
 
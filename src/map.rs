@@ -1,18 +1,52 @@
 use rltk::{ RGB, Rltk, RandomNumberGenerator, Algorithm2D, Point, BaseMap, FontCharType, SmallVec };
 use specs::{World, Entity};
 use crate::rect::*;
+use crate::prefab_builder::{prefab_level, add_prefab_pieces, TOWN, LAIR};
+use crate::cave_builder::{cave_level, forest_level};
+use crate::theme::LevelTheme;
+use crate::camera::{camera_origin, VIEWPORT_WIDTH, VIEWPORT_HEIGHT};
 use std::cmp::{max, min};
+use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-pub const MAP_WIDTH: usize = 80;
-pub const MAP_HEIGHT: usize = 43;
-pub const MAP_COUNT: usize = MAP_WIDTH * MAP_HEIGHT;
+/// Levels the player has left, by depth, so going back finds them as they were
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct VisitedLevels{
+    pub maps: HashMap<i32, Map>,
+}
+
+/// Levels grow with depth up to this size
+pub const MAX_LEVEL_WIDTH: i32 = 200;
+pub const MAX_LEVEL_HEIGHT: i32 = 150;
 
 #[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum TileType{
     Wall,
     Floor,
     DownStairs,
+    UpStairs,
+    Door,
+    OpenDoor,
+    LockedDoor,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Grass,
+    TallGrass,
+    Bridge,
+}
+
+impl TileType{
+    /// How expensive the tile is to walk onto, scaled onto the base step cost when pathfinding.
+    /// Only a weight for choosing routes: wading through water takes a normal turn, it is just avoided when there is a way around
+    pub fn movement_cost(&self) -> f32{
+        match self{
+            TileType::ShallowWater => 2.0,
+            TileType::TallGrass => 1.2,
+            TileType::Lava => 10.0,
+            _ => 1.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -25,6 +59,21 @@ pub struct Map{
     pub currently_visible_tiles: Vec<bool>,
     pub blocked_tiles: Vec<bool>,
     pub depth: i32,
+    /// decides how the level is built, drawn and populated
+    pub theme: LevelTheme,
+    /// turns left for fire burning on each tile
+    pub fire: Vec<i32>,
+    /// poison gas density on each tile
+    pub gas: Vec<i32>,
+    /// smoke density on each tile, any smoke blocks sight
+    pub smoke: Vec<i32>,
+    /// where the player is placed when arriving on the level
+    pub starting_position: (i32, i32),
+
+    /// (idx, name) of things prefabs asked the spawner to create
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub spawn_list: Vec<(usize, String)>,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -37,12 +86,61 @@ impl Map{
         (y as usize * self.width as usize) + x as usize
     }
 
-    pub fn is_idx_valid(x: i32, y: i32) -> bool{
-        x >= 0 || x < MAP_WIDTH as i32 || y >= 0 || y < MAP_HEIGHT as i32
+    /// True if x, y is inside this map
+    pub fn is_idx_valid(&self, x: i32, y: i32) -> bool{
+        x >= 0 && x < self.width && y >= 0 && y < self.height
     }
 
     pub fn load_from_save(&mut self){
-        self.tile_content = vec![Vec::new(); MAP_COUNT];
+        self.tile_content = vec![Vec::new(); self.tiles.len()];
+    }
+
+    /// Solid wall map of any size, ready for a builder to carve into
+    pub fn new(map_depth: i32, width: i32, height: i32) -> Map{
+        let tile_count = (width * height) as usize;
+
+        Map{
+            tiles: vec![TileType::Wall; tile_count],
+            rooms: Vec::new(),
+            width,
+            height,
+            revealed_tiles: vec![false; tile_count],
+            currently_visible_tiles: vec![false; tile_count],
+            blocked_tiles: vec![false; tile_count],
+            tile_content: vec![Vec::new(); tile_count],
+            depth: map_depth,
+            theme: LevelTheme::for_depth(map_depth),
+            fire: vec![0; tile_count],
+            gas: vec![0; tile_count],
+            smoke: vec![0; tile_count],
+            starting_position: (width/2, height/2),
+            spawn_list: Vec::new(),
+        }
+    }
+
+    /// Build the level for a depth with the builder its theme asks for. Deeper levels are larger
+    pub fn new_level(map_depth: i32) -> Map{
+        let width = i32::min(80 + map_depth * 20, MAX_LEVEL_WIDTH);
+        let height = i32::min(40 + map_depth * 10, MAX_LEVEL_HEIGHT);
+
+        let mut map = match LevelTheme::for_depth(map_depth){
+            LevelTheme::Town => prefab_level(&TOWN, map_depth),
+            LevelTheme::Crypt | LevelTheme::Fortress => Map::map_with_rooms_and_corridors(map_depth, width, height),
+            LevelTheme::Caves => cave_level(map_depth, width, height),
+            LevelTheme::Forest => forest_level(map_depth, width, height),
+            LevelTheme::Lair => prefab_level(&LAIR, map_depth),
+        };
+
+        // every level below the town has a way back up where the player arrives
+        if map_depth > 0{
+            let (start_x, start_y) = map.starting_position;
+            let start_idx = map.xy_idx(start_x, start_y);
+            if map.tiles[start_idx] != TileType::DownStairs{
+                map.tiles[start_idx] = TileType::UpStairs;
+            }
+        }
+
+        map
     }
 
     /// Fill room area with tiles
@@ -60,7 +158,7 @@ impl Map{
         for x in min(x1, x2)..=max(x1, x2){
             let idx = self.xy_idx(x, y);
     
-            if idx < MAP_COUNT {
+            if idx < self.tiles.len() {
                 self.tiles[idx] = TileType::Floor;
             }
         }
@@ -71,7 +169,7 @@ impl Map{
         for y in min(y1, y2)..=max(y1, y2){
             let idx = self.xy_idx(x, y);
     
-            if idx < MAP_COUNT{
+            if idx < self.tiles.len(){
                 self.tiles[idx] = TileType::Floor;
             }
         }
@@ -88,36 +186,215 @@ impl Map{
     pub fn populate_blocked_tiles(&mut self){
         self.blocked_tiles = self.tiles
             .iter()
-            .map(|tile| *tile == TileType::Wall)
+            .map(|tile| matches!(tile, TileType::Wall | TileType::Door | TileType::LockedDoor | TileType::DeepWater))
             .collect();
     }
 
+    /// Doors sit in corridor tiles right outside a room with walls on both sides of them
+    fn is_doorway(&self, x: i32, y: i32) -> bool{
+        if x < 1 || x > self.width-2 || y < 1 || y > self.height-2{
+            return false;
+        }
+
+        if self.tiles[self.xy_idx(x, y)] != TileType::Floor{
+            return false;
+        }
+
+        let is_wall = |x: i32, y: i32| self.tiles[self.xy_idx(x, y)] == TileType::Wall;
+        (is_wall(x-1, y) && is_wall(x+1, y)) || (is_wall(x, y-1) && is_wall(x, y+1))
+    }
+
+    /// Place doors, some of them locked, where corridors meet rooms. Fortresses door every entrance
+    fn add_doors(&mut self, rng: &mut RandomNumberGenerator){
+        let rooms = self.rooms.clone();
+        let door_chance = if self.theme == LevelTheme::Fortress { 1 } else { 2 };
+
+        for room in rooms.iter(){
+            let mut ring: Vec<(i32, i32)> = Vec::new();
+            for x in room.x1..=room.x2{
+                ring.push((x, room.y1-1));
+                ring.push((x, room.y2+1));
+            }
+            for y in room.y1..=room.y2{
+                ring.push((room.x1-1, y));
+                ring.push((room.x2+1, y));
+            }
+
+            for (x, y) in ring{
+                if self.is_doorway(x, y) && rng.range(0, door_chance) == 0{
+                    let idx = self.xy_idx(x, y);
+                    self.tiles[idx] = if rng.range(0, 8) == 0 { TileType::LockedDoor } else { TileType::Door };
+                }
+            }
+        }
+    }
+
+    /// Fire only takes hold on dry ground
+    pub fn can_burn(&self, idx: usize) -> bool{
+        matches!(self.tiles[idx], TileType::Floor | TileType::Grass | TileType::TallGrass | TileType::Bridge | TileType::OpenDoor)
+    }
+
+    /// Gas and smoke drift through anything that isn't a solid wall or closed door
+    pub fn can_hold_gas(&self, idx: usize) -> bool{
+        !matches!(self.tiles[idx], TileType::Wall | TileType::Door | TileType::LockedDoor)
+    }
+
+    /// Turn some rooms into meadows, lakes or lava pools. The first room is left alone for the player
+    /// and the last for the stairs
+    fn add_terrain(&mut self, rng: &mut RandomNumberGenerator){
+        if self.rooms.len() < 3{
+            return;
+        }
+
+        let rooms = self.rooms[1..self.rooms.len()-1].to_vec();
+        for room in rooms.iter(){
+            match rng.roll_dice(1, 6){
+                1 => self.apply_meadow_to_room(room, rng),
+                2 => self.apply_lake_to_room(room),
+                3 if self.depth >= 3 => self.apply_lava_to_room(room),
+                _ => {},
+            }
+        }
+    }
+
+    fn apply_meadow_to_room(&mut self, room: &Rect, rng: &mut RandomNumberGenerator){
+        for y in room.y1..=room.y2{
+            for x in room.x1..=room.x2{
+                let idx = self.xy_idx(x, y);
+                if self.tiles[idx] != TileType::Floor{
+                    continue;
+                }
+
+                self.tiles[idx] = match rng.roll_dice(1, 8){
+                    1..=2 => TileType::TallGrass,
+                    3..=7 => TileType::Grass,
+                    _ => TileType::Floor,
+                };
+            }
+        }
+    }
+
+    /// Deep water ringed by shallows with a bridge across the room's center row.
+    /// If the lake still manages to cut off part of the level the deep water is made shallow
+    fn apply_lake_to_room(&mut self, room: &Rect){
+        let (_center_x, center_y) = room.center();
+        let mut deep_tiles: Vec<usize> = Vec::new();
+
+        for y in room.y1+1..room.y2{
+            for x in room.x1+1..room.x2{
+                let idx = self.xy_idx(x, y);
+                let is_shore = x == room.x1+1 || x == room.x2-1 || y == room.y1+1 || y == room.y2-1;
+
+                self.tiles[idx] = if is_shore{
+                    TileType::ShallowWater
+                } else if y == center_y{
+                    TileType::Bridge
+                } else {
+                    deep_tiles.push(idx);
+                    TileType::DeepWater
+                };
+            }
+        }
+
+        if !self.are_rooms_connected(){
+            for idx in deep_tiles{
+                self.tiles[idx] = TileType::ShallowWater;
+            }
+        }
+    }
+
+    fn apply_lava_to_room(&mut self, room: &Rect){
+        for y in room.y1+2..room.y2-1{
+            for x in room.x1+2..room.x2-1{
+                let idx = self.xy_idx(x, y);
+                self.tiles[idx] = TileType::Lava;
+            }
+        }
+    }
+
+    /// True if every room center can be walked to from the first room
+    fn are_rooms_connected(&self) -> bool{
+        let (start_x, start_y) = self.rooms[0].center();
+        let mut reachable = vec![false; self.tiles.len()];
+        for idx in self.reachable_tiles(self.xy_idx(start_x, start_y)){
+            reachable[idx] = true;
+        }
+
+        self.rooms.iter().all(|room| {
+            let (x, y) = room.center();
+            reachable[self.xy_idx(x, y)]
+        })
+    }
+
+    /// Tiles a projectile crosses flying in a straight line from start to target, ending where it hits.
+    /// It stops at the first tile has_creature picks out, or on the last open tile before a wall or closed door
+    pub fn projectile_path(&self, start: Point, target: Point, has_creature: impl Fn(usize) -> bool) -> Vec<Point>{
+        let mut path = Vec::new();
+
+        for point in rltk::line2d(rltk::LineAlg::Bresenham, start, target).into_iter().skip(1){
+            if !self.is_idx_valid(point.x, point.y){
+                break;
+            }
+
+            let idx = self.xy_idx(point.x, point.y);
+            if matches!(self.tiles[idx], TileType::Wall | TileType::Door | TileType::LockedDoor){
+                break;
+            }
+
+            path.push(point);
+            if has_creature(idx){
+                break;
+            }
+        }
+
+        path
+    }
+
+    /// Every tile reachable from start without passing through walls or locked doors
+    pub fn reachable_tiles(&self, start_idx: usize) -> Vec<usize>{
+        let mut visited = vec![false; self.tiles.len()];
+        let mut frontier = vec![start_idx];
+        let mut reachable = Vec::new();
+        visited[start_idx] = true;
+
+        while let Some(idx) = frontier.pop(){
+            reachable.push(idx);
+
+            let x = (idx % self.width as usize) as i32;
+            let y = (idx / self.width as usize) as i32;
+            for (nx, ny) in [(x-1, y), (x+1, y), (x, y-1), (x, y+1)]{
+                if nx < 0 || nx >= self.width || ny < 0 || ny >= self.height{
+                    continue;
+                }
+
+                let next_idx = self.xy_idx(nx, ny);
+                if !visited[next_idx] && !matches!(self.tiles[next_idx], TileType::Wall | TileType::LockedDoor | TileType::DeepWater){
+                    visited[next_idx] = true;
+                    frontier.push(next_idx);
+                }
+            }
+        }
+
+        reachable
+    }
+
     pub fn clear_tile_content(&mut self){
         self.tile_content
             .iter_mut()
             .for_each(|t| t.clear());
     }
 
-    pub fn map_with_rooms_and_corridors(map_depth: i32) -> Map{
-        let mut map = Map{
-            tiles: vec![TileType::Wall; MAP_COUNT],
-            rooms: Vec::new(),
-            width: MAP_WIDTH as i32,
-            height: MAP_HEIGHT as i32,
-            revealed_tiles: vec![false; MAP_COUNT],
-            currently_visible_tiles: vec![false; MAP_COUNT],
-            blocked_tiles: vec![false; MAP_COUNT],
-            tile_content: vec![Vec::new(); MAP_COUNT],
-            depth: map_depth,
-        };
+    pub fn map_with_rooms_and_corridors(map_depth: i32, width: i32, height: i32) -> Map{
+        let mut map = Map::new(map_depth, width, height);
     
-        const MAX_ROOMS: i32 = 30;
+        // roughly 30 attempts for an 80x43 map, scaled to the map's area
+        let max_rooms: i32 = i32::max(4, (width * height) / 115);
         const MIN_SIZE: i32 = 6;
         const MAX_SIZE: i32 = 10;
     
         let mut rng = RandomNumberGenerator::new();
     
-        for _ in 0..MAX_ROOMS{
+        for _ in 0..max_rooms{
             let room_width = rng.range(MIN_SIZE, MAX_SIZE);
             let room_height = rng.range(MIN_SIZE, MAX_SIZE);
     
@@ -158,13 +435,20 @@ impl Map{
         let stair_idx = map.xy_idx(stair_position.0, stair_position.1);
         map.tiles[stair_idx] = TileType::DownStairs;
 
+        map.add_terrain(&mut rng);
+        add_prefab_pieces(&mut map, &mut rng);
+        map.add_doors(&mut rng);
+
+        map.starting_position = map.rooms[0].center();
+
         map
     }
 }
 
 impl BaseMap for Map{
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] == TileType::Wall
+        matches!(self.tiles[idx], TileType::Wall | TileType::Door | TileType::LockedDoor | TileType::TallGrass)
+            || self.smoke[idx] > 0
     }
 
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
@@ -175,17 +459,20 @@ impl BaseMap for Map{
 
         let width_usize = self.width as usize;
 
+        // step cost is scaled by the terrain being stepped onto
+        let cost = |exit_idx: usize, base: f32| base * self.tiles[exit_idx].movement_cost();
+
         // cardinal directions
-        if self.is_position_valid(x-1, y){ exits.push((idx-1, 1.0)); } 
-        if self.is_position_valid(x+1, y){ exits.push((idx+1, 1.0)); }
-        if self.is_position_valid(x, y-1){ exits.push((idx-width_usize, 1.0)); }
-        if self.is_position_valid(x, y+1){ exits.push((idx+width_usize, 1.0)); }
+        if self.is_position_valid(x-1, y){ exits.push((idx-1, cost(idx-1, 1.0))); } 
+        if self.is_position_valid(x+1, y){ exits.push((idx+1, cost(idx+1, 1.0))); }
+        if self.is_position_valid(x, y-1){ exits.push((idx-width_usize, cost(idx-width_usize, 1.0))); }
+        if self.is_position_valid(x, y+1){ exits.push((idx+width_usize, cost(idx+width_usize, 1.0))); }
 
         // diagonals
-        if self.is_position_valid(x-1, y-1){ exits.push((idx-width_usize-1, 1.45)); }
-        if self.is_position_valid(x-1, y+1){ exits.push((idx+width_usize-1, 1.45)); }
-        if self.is_position_valid(x+1, y+1){ exits.push((idx+width_usize+1, 1.45)); }
-        if self.is_position_valid(x+1, y-1){ exits.push((idx-width_usize+1, 1.45)); }
+        if self.is_position_valid(x-1, y-1){ exits.push((idx-width_usize-1, cost(idx-width_usize-1, 1.45))); }
+        if self.is_position_valid(x-1, y+1){ exits.push((idx+width_usize-1, cost(idx+width_usize-1, 1.45))); }
+        if self.is_position_valid(x+1, y+1){ exits.push((idx+width_usize+1, cost(idx+width_usize+1, 1.45))); }
+        if self.is_position_valid(x+1, y-1){ exits.push((idx-width_usize+1, cost(idx-width_usize+1, 1.45))); }
 
 
         exits
@@ -207,46 +494,91 @@ impl Algorithm2D for Map{
 
 pub fn draw_map(world: &World, context: &mut Rltk) {
     let map = world.fetch::<Map>();
-
-    let mut x = 0;
-    let mut y = 0;
+    let origin = camera_origin(world);
 
     for (idx, tile) in map.tiles.iter().enumerate(){
+        // screen position of the tile, skipping anything the camera can't see
+        let x = idx as i32 % map.width - origin.x;
+        let y = idx as i32 / map.width - origin.y;
+        if !(0..VIEWPORT_WIDTH).contains(&x) || !(0..VIEWPORT_HEIGHT).contains(&y){
+            continue;
+        }
 
         // we only want to draw tiles that have been revealed either in the past or present (meaning currently visible)
         if map.revealed_tiles[idx]{
-            let glyph: FontCharType;
+            let mut glyph: FontCharType;
             let mut fg: RGB;
 
             match tile {
                 TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.5, 0.5, 0.5);
-                    
+                    (glyph, fg) = map.theme.floor_glyph();
                 },
                 TileType::Wall => {
-                    glyph = rltk::to_cp437('#');
-                    fg = RGB::from_f32(0.0, 1.0, 0.0);
+                    (glyph, fg) = map.theme.wall_glyph();
                 },
                 TileType::DownStairs =>{
                     glyph = rltk::to_cp437('>');
                     fg = RGB::from_f32(0., 1.0, 1.0);
-                }
+                },
+                TileType::UpStairs =>{
+                    glyph = rltk::to_cp437('<');
+                    fg = RGB::from_f32(0., 1.0, 1.0);
+                },
+                TileType::Door =>{
+                    glyph = rltk::to_cp437('+');
+                    fg = RGB::named(rltk::CHOCOLATE);
+                },
+                TileType::OpenDoor =>{
+                    glyph = rltk::to_cp437('\'');
+                    fg = RGB::named(rltk::CHOCOLATE);
+                },
+                TileType::LockedDoor =>{
+                    glyph = rltk::to_cp437('+');
+                    fg = RGB::named(rltk::GOLD);
+                },
+                TileType::ShallowWater =>{
+                    glyph = rltk::to_cp437('~');
+                    fg = RGB::named(rltk::SKYBLUE);
+                },
+                TileType::DeepWater =>{
+                    glyph = rltk::to_cp437('≈');
+                    fg = RGB::named(rltk::BLUE);
+                },
+                TileType::Lava =>{
+                    glyph = rltk::to_cp437('~');
+                    fg = RGB::named(rltk::ORANGE_RED);
+                },
+                TileType::Grass =>{
+                    glyph = rltk::to_cp437('"');
+                    fg = RGB::named(rltk::FOREST_GREEN);
+                },
+                TileType::TallGrass =>{
+                    glyph = rltk::to_cp437('♣');
+                    fg = RGB::named(rltk::GREEN);
+                },
+                TileType::Bridge =>{
+                    glyph = rltk::to_cp437('=');
+                    fg = RGB::named(rltk::CHOCOLATE);
+                },
             }
 
+            let mut bg = RGB::from_f32(0., 0., 0.);
+
             // if something is revealed meaning we've seen it, but we can't currently see it. It's not in our current fov
             if !map.currently_visible_tiles[idx]{
                 fg = fg.to_greyscale();
+            } else if map.fire[idx] > 0{
+                glyph = rltk::to_cp437('^');
+                fg = RGB::named(rltk::YELLOW);
+                bg = RGB::named(rltk::DARK_RED);
+            } else if map.gas[idx] > 0{
+                bg = RGB::named(rltk::DARK_GREEN);
+            } else if map.smoke[idx] > 0{
+                glyph = rltk::to_cp437('░');
+                fg = RGB::named(rltk::GREY);
             }
 
-            context.set(x, y, fg, RGB::from_f32(0., 0., 0.), glyph);
-        }
-
-        // update x, y for terminal
-        x += 1;
-        if x > MAP_WIDTH-1{
-            x = 0;
-            y += 1;
+            context.set(x, y, fg, bg, glyph);
         }
     }
 
@@ -1,6 +1,6 @@
 use specs::prelude::*;
 
-use crate::{WantsToMelee, CombatStats, SuffersDamage, Name, GameLog, MeleePowerBonus, DefenseBonus, Equipped};
+use crate::{LogEntry, WantsToMelee, CombatStats, SuffersDamage, DamageInstance, Name, GameLog, MeleePowerBonus, DefenseBonus, Equipped, EquipmentSlot, MeleeDamageType, DamageType, with_article};
 
 pub struct MeleeCombatSystem{ }
 
@@ -14,10 +14,11 @@ impl<'a> System<'a> for MeleeCombatSystem{
                         ReadStorage<'a, MeleePowerBonus>,
                         ReadStorage<'a, DefenseBonus>,
                         ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleeDamageType>,
                     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut wants_to_melee, combat_stats, mut suffers_damage, names, entities, mut gamelog, melee_bonus, defense_bonus, equipped,) = data;
+        let (mut wants_to_melee, combat_stats, mut suffers_damage, names, entities, mut gamelog, melee_bonus, defense_bonus, equipped, melee_damage_types) = data;
 
         for (stats, name, entity, wants_to_melee) in (&combat_stats, &names, &entities, &mut wants_to_melee).join(){
 
@@ -35,6 +36,16 @@ impl<'a> System<'a> for MeleeCombatSystem{
                 }
             }
 
+            let weapon = (&entities, &equipped).join()
+                .find(|(_item, equipped)| equipped.owner == entity && equipped.slot == EquipmentSlot::Melee)
+                .map(|(item, _equipped)| item);
+
+            // a wielded weapon decides the kind of damage, otherwise the attacker's own claws or fire do
+            let damage_type = weapon.and_then(|weapon| melee_damage_types.get(weapon))
+                .or_else(|| melee_damage_types.get(entity))
+                .map(|melee_type| melee_type.damage_type)
+                .unwrap_or(DamageType::Physical);
+
             if let Some(target_combat_stats) = combat_stats.get(wants_to_melee.target){
                 if target_combat_stats.hp > 0{
 
@@ -49,10 +60,17 @@ impl<'a> System<'a> for MeleeCombatSystem{
                     let damage = i32::max(0, (stats.attack + offensive_bonus) - (target_combat_stats.defense + defensive_bonus));
 
                     if damage == 0{
-                        gamelog.entries.push(format!("{} could not damage {}...", name.name, target_name));
+                        gamelog.add(LogEntry::new().name(&name.name).text(" could not damage ").name(&target_name).text("..."));
                     } else {
-                        gamelog.entries.push(format!("{} hits {} for {} hp!", name.name, target_name, damage));
-                        SuffersDamage::new_damage(&mut suffers_damage, wants_to_melee.target, damage);
+                        // the damage system reports the hit once resistances have changed the amount
+                        SuffersDamage::new_damage(&mut suffers_damage, wants_to_melee.target, DamageInstance{
+                            amount: damage,
+                            damage_type,
+                            source: Some(entity),
+                            item: weapon,
+                            cause: with_article(&name.name),
+                            melee: true,
+                        });
                     }
                     
                 }
@@ -1,4 +1,5 @@
 use super::*;
+use std::collections::HashMap;
 
 pub struct MonsterAI{ }
 
@@ -13,6 +14,17 @@ impl<'a> System<'a> for MonsterAI{
                         ReadExpect<'a, Entity>, // gets player entity resource
                         ReadExpect<'a, RunState>,
                         WriteStorage<'a, IsConfused>,
+                        WriteStorage<'a, Alerted>,
+                        WriteStorage<'a, EntityMoved>,
+                        // boss abilities
+                        ( WriteStorage<'a, UsesAbility>,
+                          WriteStorage<'a, SummonsMinions>,
+                          WriteStorage<'a, WantsToUseItem>,
+                          ReadStorage<'a, Name>,
+                          WriteExpect<'a, GameLog>,
+                          Read<'a, LazyUpdate>,
+                        ),
+                        WriteStorage<'a, Grudge>,
                     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -26,10 +38,20 @@ impl<'a> System<'a> for MonsterAI{
              player_entity,
              run_state,
              mut is_confused,
+             mut alerted,
+             mut entity_moved,
+             (mut uses_ability, mut summons_minions, mut wants_to_use_item, names, mut gamelog, lazy),
+             mut grudges,
             ) = data;
 
         if *run_state != RunState::MonsterTurn{ return; }
 
+        // where each grudge target stands, positions are borrowed mutably while the monsters act
+        let grudge_targets: HashMap<Entity, (Entity, Point)> = (&grudges, &entities).join()
+            .filter(|(grudge, _monster)| entities.is_alive(grudge.target))
+            .filter_map(|(grudge, monster)| position.get(grudge.target).map(|pos| (monster, (grudge.target, Point{ x: pos.x, y: pos.y }))))
+            .collect();
+
         for(_monster, fov, pos, entity) in (&monsters, &mut fov, &mut position, &entities).join(){
 
             let mut monster_can_act = true;
@@ -47,13 +69,72 @@ impl<'a> System<'a> for MonsterAI{
             if !monster_can_act{ return; }
 
             let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point { x: pos.x, y: pos.y }, *player_position);
-            if distance < 1.5{
-                wants_to_melee.insert(entity, WantsToMelee { target: *player_entity })
-                    .expect("Could not add wants to melee component to monster with target player.");
-            } else if fov.visible_tiles.contains(&*player_position){
+            let sees_player = fov.visible_tiles.contains(&*player_position);
+            let name = names.get(entity).map(|n| n.name.clone()).unwrap_or_default();
+
+            // abilities recharge while the player is in sight and are used instead of moving or attacking
+            let mut used_ability = false;
+            if let Some(ability) = uses_ability.get_mut(entity){
+                if sees_player && ability.timer > 0{
+                    ability.timer -= 1;
+                } else if sees_player && distance > 1.5 && distance <= ability.range as f32{
+                    wants_to_use_item.insert(entity, WantsToUseItem { item: ability.ability, target: Some(*player_position) })
+                        .expect("Could not add intent to use ability.");
+                    ability.timer = ability.cooldown;
+                    used_ability = true;
+
+                    let ability_name = names.get(ability.ability).map(|n| n.name.clone()).unwrap_or_default();
+                    gamelog.add(LogEntry::new().name(&name).text(" uses ").warning(ability_name).text("!"));
+                }
+            }
+
+            if let Some(summoner) = summons_minions.get_mut(entity){
+                if sees_player && summoner.timer > 0{
+                    summoner.timer -= 1;
+                } else if sees_player && !used_ability{
+                    let mut summoned = 0;
+                    for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)]{
+                        let (x, y) = (pos.x + dx, pos.y + dy);
+                        if summoned >= summoner.count || !map.is_idx_valid(x, y){
+                            continue;
+                        }
+
+                        let idx = map.xy_idx(x, y);
+                        if map.blocked_tiles[idx] || map.tiles[idx] == TileType::Lava{
+                            continue;
+                        }
+
+                        // spawning needs the whole world so it waits until the systems are done
+                        map.blocked_tiles[idx] = true;
+                        let minion = summoner.minion.clone();
+                        let depth = map.depth;
+                        lazy.exec_mut(move |world| {
+                            spawn_named_entity(world, &minion, x, y, depth);
+                        });
+                        summoned += 1;
+                    }
+
+                    summoner.timer = summoner.cooldown;
+                    used_ability = true;
+                    gamelog.add(LogEntry::new().name(&name).warning(" calls for help!"));
+                }
+            }
+
+            // using an ability spends the monster's turn
+            // a monster holding a grudge goes after whoever hurt it while it can see them
+            let grudge = grudge_targets.get(&entity).filter(|(_target, target_pos)| fov.visible_tiles.contains(target_pos));
+            let (target, target_pos, distance, sees_target) = match grudge{
+                Some((target, target_pos)) => (*target, *target_pos, rltk::DistanceAlg::Pythagoras.distance2d(Point { x: pos.x, y: pos.y }, *target_pos), true),
+                None => (*player_entity, *player_position, distance, sees_player),
+            };
+
+            if !used_ability && distance < 1.5{
+                wants_to_melee.insert(entity, WantsToMelee { target })
+                    .expect("Could not add wants to melee component to monster.");
+            } else if !used_ability && (sees_target || alerted.get(entity).is_some()){
                 let monster_path_to_player = rltk::a_star_search(
                     map.xy_idx(pos.x, pos.y),
-                    map.xy_idx(player_position.x, player_position.y),
+                    map.xy_idx(target_pos.x, target_pos.y),
                     &*map);
 
                 if monster_path_to_player.success && monster_path_to_player.steps.len() > 1 {
@@ -69,8 +150,30 @@ impl<'a> System<'a> for MonsterAI{
                     map.blocked_tiles[next_idx] = true;
 
                     fov.needs_update = true;
+                    entity_moved.insert(entity, EntityMoved{ })
+                        .expect("Could not mark monster as moved.");
                 }
             }
+
+            // alarms wear off and the monster goes back to only chasing what it sees
+            let mut alert_expired = false;
+            if let Some(alert) = alerted.get_mut(entity){
+                alert.turns -= 1;
+                alert_expired = alert.turns <= 0;
+            }
+            if alert_expired{
+                alerted.remove(entity);
+            }
+
+            // grudges fade, or end early once the target is gone
+            let mut grudge_expired = false;
+            if let Some(grudge) = grudges.get_mut(entity){
+                grudge.turns -= 1;
+                grudge_expired = grudge.turns <= 0 || !grudge_targets.contains_key(&entity);
+            }
+            if grudge_expired{
+                grudges.remove(entity);
+            }
         }
     }
 }
\ No newline at end of file
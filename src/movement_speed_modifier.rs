@@ -23,7 +23,7 @@ impl<'a> System<'a> for MovementSpeedModifier{
                 expired_move_speed.push(entity);
 
                 if entity == *player_entity{
-                    gamelog.entries.push("Your move speed has expired...".to_string());
+                    gamelog.log("Your move speed has expired...");
                 }
             }
         }
@@ -1,9 +1,9 @@
 use specs::prelude::*;
 use std::cmp::{max, min};
-use rltk::{Rltk, VirtualKeyCode};
+use rltk::{Rltk, VirtualKeyCode, RandomNumberGenerator};
 use super::*;
 
-pub fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World){
+pub fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) -> RunState{
     let mut positions = world.write_storage::<Position>();
     let mut players = world.write_storage::<Player>();
     let mut fov = world.write_storage::<FOV>();
@@ -11,10 +11,15 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World){
     let combat_stats = world.read_storage::<CombatStats>();
     let mut wants_to_melee = world.write_storage::<WantsToMelee>();
     let mut has_movement_speed_modifier = world.write_storage::<HasMovementSpeedModifier>();
+    let shopkeepers = world.read_storage::<Shopkeeper>();
+    let mut entity_moved = world.write_storage::<EntityMoved>();
+    let keys = world.read_storage::<Key>();
+    let backpacks = world.read_storage::<InBackpack>();
+    let mut gamelog = world.write_resource::<GameLog>();
 
     let entities = world.entities();
 
-    let map = world.fetch::<Map>();
+    let mut map = world.fetch_mut::<Map>();
 
     for (_player, pos, fov, entity) in (&mut players, &mut positions, &mut fov, &entities).join(){
         let mut modified_delta_x = delta_x;
@@ -31,16 +36,56 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World){
         // the max possible value of pos.x is 78 and pos.y 48
         let destination_map_idx = map.xy_idx(pos.x+modified_delta_x, pos.y-modified_delta_y);
 
+        // walking into a shopkeeper opens their shop instead of moving
+        for potential_vendor in map.tile_content[destination_map_idx].iter(){
+            if shopkeepers.get(*potential_vendor).is_some(){
+                return RunState::ShowShop { vendor: *potential_vendor, mode: ShopMode::Buy };
+            }
+        }
+
         // for each entity in the destination tile, see if they have combat stats. If they do fight them
         for potential_target in map.tile_content[destination_map_idx].iter(){
             if let Some(_target) = combat_stats.get(*potential_target){
                 // add wants to melee component with target as potential target
                 wants_to_melee.insert(entity, WantsToMelee { target: *potential_target })
                     .expect("Cannot add component wants_to_melee to taget");
-                return; // dont want to continue to move on top of target
+                return RunState::PlayerTurn; // dont want to continue to move on top of target
             }
         }
 
+        // bumping into a door opens it rather than moving through it
+        match map.tiles[destination_map_idx]{
+            TileType::Door => {
+                map.tiles[destination_map_idx] = TileType::OpenDoor;
+                map.blocked_tiles[destination_map_idx] = false;
+                fov.needs_update = true;
+                gamelog.log("You open the door.");
+                return RunState::PlayerTurn;
+            },
+            TileType::LockedDoor => {
+                let key = (&entities, &keys, &backpacks).join()
+                    .find(|(_key_entity, _key, backpack)| backpack.owner == entity)
+                    .map(|(key_entity, _key, _backpack)| key_entity);
+
+                match key{
+                    None => {
+                        gamelog.log("The door is locked.");
+                        return RunState::AwaitingInput;
+                    },
+                    Some(key) => {
+                        entities.delete(key)
+                            .expect("Could not use up key.");
+                        map.tiles[destination_map_idx] = TileType::OpenDoor;
+                        map.blocked_tiles[destination_map_idx] = false;
+                        fov.needs_update = true;
+                        gamelog.log("You unlock the door with a key.");
+                        return RunState::PlayerTurn;
+                    },
+                }
+            },
+            _ => {},
+        }
+
 
         if !map.blocked_tiles[destination_map_idx]{
             // neat way to do create valid bounds for min and max
@@ -53,58 +98,149 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World){
 
             // player moved so fov changed
             fov.needs_update = true;
+
+            entity_moved.insert(entity, EntityMoved{ })
+                .expect("Could not mark player as moved.");
         }
     }
+
+    RunState::PlayerTurn
+}
+
+/// Which spell a number key casts, 1 being the first
+fn quick_cast_slot(key: VirtualKeyCode) -> Option<usize>{
+    use VirtualKeyCode::*;
+    [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9].iter().position(|k| *k == key)
 }
 
 pub fn player_input(game_state: &mut State, context: &mut Rltk) -> RunState{
-    match context.key {
+    // while walking on its own any key or click hands control back to the player
+    if game_state.world.fetch::<AutoMove>().mode.is_some(){
+        let key_pressed = context.key.is_some_and(|key| !matches!(key, VirtualKeyCode::LShift | VirtualKeyCode::RShift));
+        if key_pressed || context.left_click{
+            game_state.world.write_resource::<AutoMove>().mode = None;
+            return RunState::AwaitingInput;
+        }
+        return auto_move_step(&mut game_state.world);
+    }
+
+    // clicking next to the player steps or attacks there, clicking further away travels
+    if context.left_click{
+        let (mouse_x, mouse_y) = context.mouse_pos();
+        if mouse_x < VIEWPORT_WIDTH && mouse_y < VIEWPORT_HEIGHT{
+            let destination = screen_to_map(&game_state.world, Point::new(mouse_x, mouse_y));
+            let player_position = *game_state.world.fetch::<Point>();
+            let (delta_x, delta_y) = (destination.x - player_position.x, destination.y - player_position.y);
+
+            if delta_x == 0 && delta_y == 0{
+                return RunState::AwaitingInput;
+            }
+            if delta_x.abs() <= 1 && delta_y.abs() <= 1{
+                return try_move_player(delta_x, -delta_y, &mut game_state.world);
+            }
+            return start_auto_move(&mut game_state.world, AutoMoveMode::Travel{ x: destination.x, y: destination.y });
+        }
+    }
+
+    let action = match context.key {
         None => { return RunState::AwaitingInput; }, // if no key pressed, no update for game to run on
-        Some(key) => match key {
-            VirtualKeyCode::A |
-            VirtualKeyCode::Left => try_move_player(-1, 0, &mut game_state.world),
+        Some(key) => game_state.world.fetch::<Keymap>().game_action(key),
+    };
+
+    // number keys cast the spells in the order the cast menu lists them
+    if action.is_none(){
+        if let Some(slot) = context.key.and_then(quick_cast_slot){
+            let player_entity = *game_state.world.fetch::<Entity>();
+            if let Some(spell) = known_spells(&game_state.world, player_entity).get(slot){
+                return begin_cast(&mut game_state.world, *spell);
+            }
+            return RunState::AwaitingInput;
+        }
+    }
 
-            VirtualKeyCode::W |
-            VirtualKeyCode::Up => try_move_player(0, 1, &mut game_state.world),
+    // holding shift runs instead of taking a single step
+    if context.shift{
+        let direction = match action{
+            Some(Action::MoveWest) => Some((-1, 0)),
+            Some(Action::MoveEast) => Some((1, 0)),
+            Some(Action::MoveNorth) => Some((0, -1)),
+            Some(Action::MoveSouth) => Some((0, 1)),
+            Some(Action::MoveNorthWest) => Some((-1, -1)),
+            Some(Action::MoveNorthEast) => Some((1, -1)),
+            Some(Action::MoveSouthWest) => Some((-1, 1)),
+            Some(Action::MoveSouthEast) => Some((1, 1)),
+            _ => None,
+        };
+
+        if let Some((delta_x, delta_y)) = direction{
+            return start_run(&mut game_state.world, delta_x, delta_y);
+        }
+    }
 
-            VirtualKeyCode::D |
-            VirtualKeyCode::Right => try_move_player(1, 0, &mut game_state.world),
+    match action {
+        Some(Action::MoveWest) => return try_move_player(-1, 0, &mut game_state.world),
 
-            VirtualKeyCode::S |
-            VirtualKeyCode::Down => try_move_player(0, -1, &mut game_state.world),
+        Some(Action::MoveNorth) => return try_move_player(0, 1, &mut game_state.world),
 
-            // diagonals
-            VirtualKeyCode::Q => try_move_player(-1, 1, &mut game_state.world),
+        Some(Action::MoveEast) => return try_move_player(1, 0, &mut game_state.world),
 
-            VirtualKeyCode::E => try_move_player(1, 1, &mut game_state.world),
+        Some(Action::MoveSouth) => return try_move_player(0, -1, &mut game_state.world),
 
-            VirtualKeyCode::C => try_move_player(1, -1, &mut game_state.world),
+        // diagonals
+        Some(Action::MoveNorthWest) => return try_move_player(-1, 1, &mut game_state.world),
 
-            VirtualKeyCode::Z => try_move_player(-1, -1, &mut game_state.world),
+        Some(Action::MoveNorthEast) => return try_move_player(1, 1, &mut game_state.world),
 
-            // pick up item
-            VirtualKeyCode::G => pickup_item(&mut game_state.world),
+        Some(Action::MoveSouthEast) => return try_move_player(1, -1, &mut game_state.world),
 
-            VirtualKeyCode::I => return RunState::InInventory,
+        Some(Action::MoveSouthWest) => return try_move_player(-1, -1, &mut game_state.world),
 
-            VirtualKeyCode::R => return RunState::ShowDropItem,
+        // pick up item
+        Some(Action::PickUp) => pickup_item(&mut game_state.world),
 
-            VirtualKeyCode::Escape => return RunState::SaveGame,
+        Some(Action::Inventory) => return RunState::InInventory,
 
-            VirtualKeyCode::M => return RunState::MainMenu { menu_selection: MainMenuSelection::NewGame },
+        Some(Action::Drop) => return RunState::ShowDropItem,
 
-            VirtualKeyCode::U => return RunState::ShowUnequipItem,
-            
-            VirtualKeyCode::Period => {
-                if try_next_level(&mut game_state.world){
-                    return RunState::DescendFloor;
-                }
-            },
+        Some(Action::Throw) => return RunState::ShowThrowItem,
+
+        Some(Action::CastSpell) => return RunState::ShowCastSpell,
 
-            VirtualKeyCode::Space => return skip_turn(&mut game_state.world),
+        Some(Action::SaveAndQuit) => return RunState::SaveGame,
+
+        Some(Action::MainMenu) => return RunState::MainMenu { menu_selection: MainMenuSelection::NewGame },
+
+        Some(Action::Unequip) => return RunState::ShowUnequipItem,
+
+        Some(Action::MessageLog) => return RunState::ShowLog { scroll: 0 },
+
+        Some(Action::Look) => return RunState::Looking { index: 0 },
+
+        Some(Action::Help) => return RunState::ShowHelp,
+
+        Some(Action::AutoExplore) => return start_auto_move(&mut game_state.world, AutoMoveMode::Explore),
+
+        Some(Action::TravelToStairs) => return travel_to_stairs(&mut game_state.world),
+
+        Some(Action::Descend) => {
+            if try_next_level(&mut game_state.world){
+                return RunState::DescendFloor;
+            }
+        },
 
-            _ => { return RunState::AwaitingInput; }, // if irrelevant key pressed, nothing for game to update on
+        Some(Action::Ascend) => {
+            if try_previous_level(&mut game_state.world){
+                return RunState::AscendFloor;
+            }
         },
+
+        Some(Action::Wait) => return skip_turn(&mut game_state.world),
+
+        Some(Action::Search) => search_for_traps(&mut game_state.world),
+
+        Some(Action::CloseDoor) => return close_door(&mut game_state.world),
+
+        _ => { return RunState::AwaitingInput; }, // if irrelevant key pressed, nothing for game to update on
     }
 
     // if player just moved, we need to run the game to update stuff
@@ -129,7 +265,7 @@ pub fn pickup_item(world: &mut World){
     }
 
     match target_item{
-        None => gamelog.entries.push("Nothing to pick up here...".to_string()),
+        None => gamelog.log("Nothing to pick up here..."),
         Some(item) => {
             pick_up_items.insert(item, WantsToPickUpItem { collected_by: *player_entity, item })
                 .expect("Unable to pick up item by player.");
@@ -148,11 +284,26 @@ pub fn try_next_level(world: &mut World) -> bool{
         return true;
     } else {
         let mut gamelog = world.fetch_mut::<GameLog>();
-        gamelog.entries.push("No stairs to descend...".to_string());
+        gamelog.log("No stairs to descend...");
         return false;
     }
 }
 
+pub fn try_previous_level(world: &mut World) -> bool{
+    let player_position = world.fetch::<Point>();
+    let map = world.fetch::<Map>();
+
+    let player_idx = map.xy_idx(player_position.x, player_position.y);
+
+    if map.tiles[player_idx] == TileType::UpStairs{
+        true
+    } else {
+        let mut gamelog = world.fetch_mut::<GameLog>();
+        gamelog.log("No stairs to climb...");
+        false
+    }
+}
+
 pub fn skip_turn(world: &mut World) -> RunState{
     let player_entity = world.fetch::<Entity>();
     let fov = world.read_storage::<FOV>();
@@ -180,4 +331,68 @@ pub fn skip_turn(world: &mut World) -> RunState{
     }
 
     RunState::PlayerTurn
-}
\ No newline at end of file
+}
+
+/// Look around for hidden traps close to the player. Each one nearby has a chance to be found
+pub fn search_for_traps(world: &mut World){
+    let player_position = *world.fetch::<Point>();
+    let entities = world.entities();
+    let positions = world.read_storage::<Position>();
+    let names = world.read_storage::<Name>();
+    let mut hidden = world.write_storage::<Hidden>();
+    let mut rng = world.write_resource::<RandomNumberGenerator>();
+    let mut gamelog = world.write_resource::<GameLog>();
+
+    let mut found: Vec<Entity> = Vec::new();
+    for (entity, _hidden, pos) in (&entities, &hidden, &positions).join(){
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(player_position, Point::new(pos.x, pos.y));
+        if distance < 3.0 && rng.roll_dice(1, 3) == 1{
+            found.push(entity);
+        }
+    }
+
+    if found.is_empty(){
+        gamelog.log("You search but find nothing.");
+    }
+
+    for entity in found{
+        hidden.remove(entity);
+        if let Some(name) = names.get(entity){
+            gamelog.add(LogEntry::new().text("You spot a ").name(&name.name).warning("!"));
+        }
+    }
+}
+
+/// Close an open door next to the player as long as nothing is standing in it
+pub fn close_door(world: &mut World) -> RunState{
+    let player_position = *world.fetch::<Point>();
+    let mut map = world.fetch_mut::<Map>();
+    let mut gamelog = world.write_resource::<GameLog>();
+
+    for delta_x in -1..=1{
+        for delta_y in -1..=1{
+            let x = player_position.x + delta_x;
+            let y = player_position.y + delta_y;
+            if x < 0 || x >= map.width || y < 0 || y >= map.height{
+                continue;
+            }
+
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::OpenDoor && map.tile_content[idx].is_empty(){
+                map.tiles[idx] = TileType::Door;
+                map.blocked_tiles[idx] = true;
+                gamelog.log("You close the door.");
+
+                let player_entity = world.fetch::<Entity>();
+                if let Some(fov) = world.write_storage::<FOV>().get_mut(*player_entity){
+                    fov.needs_update = true;
+                }
+
+                return RunState::PlayerTurn;
+            }
+        }
+    }
+
+    gamelog.log("There is no open door to close.");
+    RunState::AwaitingInput
+}
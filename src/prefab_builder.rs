@@ -0,0 +1,184 @@
+use rltk::RandomNumberGenerator;
+
+use crate::{Map, TileType, Rect, FINAL_DEPTH};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum PrefabKind{
+    /// A whole hand made level
+    Level,
+    /// Decoration stamped inside a room, spaces leave the room untouched
+    RoomSection,
+    /// A walled off set piece with its own monsters and loot
+    Vault,
+}
+
+/// ASCII art template with the depths it can show up at
+pub struct Prefab{
+    pub kind: PrefabKind,
+    pub template: &'static str,
+    pub min_depth: i32,
+    pub max_depth: i32,
+}
+
+pub const TOWN: Prefab = Prefab{
+    kind: PrefabKind::Level,
+    template: include_str!("../prefabs/town.txt"),
+    min_depth: 0,
+    max_depth: 0,
+};
+
+/// The final level, a fortified hall at the bottom of the dungeon with no way further down
+pub const LAIR: Prefab = Prefab{
+    kind: PrefabKind::Level,
+    template: include_str!("../prefabs/lair.txt"),
+    min_depth: FINAL_DEPTH,
+    max_depth: FINAL_DEPTH,
+};
+
+/// Sections and vaults that can be stamped into generated levels
+pub const PREFAB_PIECES: &[Prefab] = &[
+    Prefab{
+        kind: PrefabKind::RoomSection,
+        template: include_str!("../prefabs/pillared_hall.txt"),
+        min_depth: 1,
+        max_depth: 100,
+    },
+    Prefab{
+        kind: PrefabKind::RoomSection,
+        template: include_str!("../prefabs/shrine.txt"),
+        min_depth: 1,
+        max_depth: 100,
+    },
+    Prefab{
+        kind: PrefabKind::Vault,
+        template: include_str!("../prefabs/goblin_den.txt"),
+        min_depth: 1,
+        max_depth: 4,
+    },
+    Prefab{
+        kind: PrefabKind::Vault,
+        template: include_str!("../prefabs/orc_armoury.txt"),
+        min_depth: 3,
+        max_depth: 100,
+    },
+];
+
+impl Prefab{
+    fn rows(&self) -> Vec<Vec<char>>{
+        self.template.lines()
+            .map(|line| line.trim_end_matches('\r').chars().collect())
+            .collect()
+    }
+
+    pub fn dimensions(&self) -> (i32, i32){
+        let rows = self.rows();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        (width as i32, rows.len() as i32)
+    }
+}
+
+/// Legend for the terrain glyphs used in prefab files
+fn glyph_to_tile(glyph: char) -> Option<TileType>{
+    match glyph{
+        '#' => Some(TileType::Wall),
+        '.' => Some(TileType::Floor),
+        '>' => Some(TileType::DownStairs),
+        '<' => Some(TileType::UpStairs),
+        '+' => Some(TileType::Door),
+        '~' => Some(TileType::ShallowWater),
+        'W' => Some(TileType::DeepWater),
+        'L' => Some(TileType::Lava),
+        '"' => Some(TileType::Grass),
+        ';' => Some(TileType::TallGrass),
+        '=' => Some(TileType::Bridge),
+        _ => None,
+    }
+}
+
+/// Legend for the glyphs that spawn something on a floor tile, named the way the spawner knows them
+fn glyph_to_spawn(glyph: char) -> Option<&'static str>{
+    match glyph{
+        'g' => Some("Goblin"),
+        'o' => Some("Orc"),
+        '!' => Some("Health Potion"),
+        '?' => Some("Magic Missile Scroll"),
+        '/' => Some("Dagger"),
+        '(' => Some("Shield"),
+        '$' => Some("Gold"),
+        '^' => Some("Spike Trap"),
+        'k' => Some("Key"),
+        'S' => Some("Shopkeeper"),
+        '&' => Some("Infernal Warlord"),
+        _ => None,
+    }
+}
+
+/// Copy a prefab onto the map with its top left corner at x, y.
+/// Spaces keep whatever was there, spawn glyphs become floor and are queued for the spawner
+pub fn stamp_prefab(map: &mut Map, prefab: &Prefab, x: i32, y: i32){
+    for (dy, row) in prefab.rows().iter().enumerate(){
+        for (dx, glyph) in row.iter().enumerate(){
+            let (tile_x, tile_y) = (x + dx as i32, y + dy as i32);
+            if *glyph == ' ' || !map.is_idx_valid(tile_x, tile_y){
+                continue;
+            }
+
+            let idx = map.xy_idx(tile_x, tile_y);
+            if *glyph == '@'{
+                map.tiles[idx] = TileType::Floor;
+                map.starting_position = (tile_x, tile_y);
+            } else if let Some(spawn) = glyph_to_spawn(*glyph){
+                map.tiles[idx] = TileType::Floor;
+                map.spawn_list.push((idx, spawn.to_string()));
+            } else if let Some(tile) = glyph_to_tile(*glyph){
+                map.tiles[idx] = tile;
+            }
+        }
+    }
+}
+
+/// A map made entirely from a level prefab
+pub fn prefab_level(prefab: &Prefab, map_depth: i32) -> Map{
+    let (width, height) = prefab.dimensions();
+    let mut map = Map::new(map_depth, width, height);
+    stamp_prefab(&mut map, prefab, 0, 0);
+    map
+}
+
+/// Stamp sections and vaults into some of a generated level's rooms.
+/// Pieces sit inside the room's outer ring of floor so corridors into the room stay connected.
+/// The first room is left for the player and the last for the stairs
+pub fn add_prefab_pieces(map: &mut Map, rng: &mut RandomNumberGenerator){
+    if map.rooms.len() < 3{
+        return;
+    }
+
+    let rooms: Vec<Rect> = map.rooms[1..map.rooms.len()-1].to_vec();
+    for room in rooms.iter(){
+        let kind = match rng.roll_dice(1, 8){
+            1 => PrefabKind::Vault,
+            2..=3 => PrefabKind::RoomSection,
+            _ => continue,
+        };
+
+        let inner_width = room.x2 - room.x1 - 1;
+        let inner_height = room.y2 - room.y1 - 1;
+        let candidates: Vec<&Prefab> = PREFAB_PIECES.iter()
+            .filter(|prefab| prefab.kind == kind && map.depth >= prefab.min_depth && map.depth <= prefab.max_depth)
+            .filter(|prefab| {
+                let (width, height) = prefab.dimensions();
+                width <= inner_width && height <= inner_height
+            })
+            .collect();
+
+        if candidates.is_empty(){
+            continue;
+        }
+
+        let prefab = candidates[rng.range(0, candidates.len())];
+        let (width, height) = prefab.dimensions();
+        let x = room.x1 + 1 + rng.range(0, inner_width - width + 1);
+        let y = room.y1 + 1 + rng.range(0, inner_height - height + 1);
+        stamp_prefab(map, prefab, x, y);
+    }
+}
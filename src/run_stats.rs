@@ -0,0 +1,145 @@
+use std::{collections::BTreeMap, fs::File, io::{self, Write}, time::{SystemTime, UNIX_EPOCH}};
+
+use rltk::Point;
+use serde::{Serialize, Deserialize};
+use specs::prelude::*;
+
+use crate::{Map, TileType, Name, InBackpack, Equipped, Wallet, CombatStats};
+
+/// Everything worth remembering about the current run, shown when it ends
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats{
+    pub turns: i32,
+    /// monster name to how many of them the player killed
+    pub kills: BTreeMap<String, i32>,
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    pub items_used: i32,
+    pub deepest_depth: i32,
+    /// what landed the killing blow and where, "a Goblin on depth 4"
+    pub cause_of_death: Option<String>,
+}
+
+impl RunStats{
+    pub fn add_kill(&mut self, monster_name: &str){
+        *self.kills.entry(monster_name.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn total_kills(&self) -> i32{
+        self.kills.values().sum()
+    }
+
+    /// Lines summing up the run, shared by the end screens and the morgue file
+    pub fn summary(&self) -> Vec<String>{
+        let mut lines = vec![
+            format!("Turns taken: {}", self.turns),
+            format!("Deepest depth: {}", self.deepest_depth),
+            format!("Monsters killed: {}", self.total_kills()),
+            format!("Damage dealt: {}", self.damage_dealt),
+            format!("Damage taken: {}", self.damage_taken),
+            format!("Items used: {}", self.items_used),
+        ];
+
+        if let Some(cause) = &self.cause_of_death{
+            lines.push(format!("Killed by {}", cause));
+        }
+
+        lines
+    }
+}
+
+/// Map glyphs as plain text for the morgue file
+fn tile_char(tile: TileType) -> char{
+    match tile{
+        TileType::Wall => '#',
+        TileType::Floor => '.',
+        TileType::DownStairs => '>',
+        TileType::UpStairs => '<',
+        TileType::Door | TileType::LockedDoor => '+',
+        TileType::OpenDoor => '\'',
+        TileType::ShallowWater | TileType::Lava => '~',
+        TileType::DeepWater => 'W',
+        TileType::Grass => '"',
+        TileType::TallGrass => ';',
+        TileType::Bridge => '=',
+    }
+}
+
+/// Write a plain text record of a finished run: the stats, what the player carried
+/// and the part of the final level they had seen. Returns the file name, or why it could not be written
+pub fn write_morgue_file(world: &World) -> io::Result<String>{
+    let stats = world.fetch::<RunStats>();
+    let map = world.fetch::<Map>();
+    let player_entity = world.fetch::<Entity>();
+    let player_position = world.fetch::<Point>();
+    let names = world.read_storage::<Name>();
+    let backpacks = world.read_storage::<InBackpack>();
+    let equipped = world.read_storage::<Equipped>();
+    let wallets = world.read_storage::<Wallet>();
+    let combat_stats = world.read_storage::<CombatStats>();
+
+    let mut lines = vec!["Morgue file".to_string(), String::new()];
+    lines.push(format!("Died on depth {}", map.depth));
+    lines.extend(stats.summary());
+    if let Some(player_stats) = combat_stats.get(*player_entity){
+        lines.push(format!("Max health: {}", player_stats.max_hp));
+    }
+    if let Some(wallet) = wallets.get(*player_entity){
+        lines.push(format!("Gold: {}", wallet.gold));
+    }
+
+    lines.push(String::new());
+    lines.push("Kills:".to_string());
+    for (name, count) in stats.kills.iter(){
+        lines.push(format!("  {} x{}", name, count));
+    }
+
+    lines.push(String::new());
+    lines.push("Equipped:".to_string());
+    for (name, equip) in (&names, &equipped).join(){
+        if equip.owner == *player_entity{
+            lines.push(format!("  {}", name.name));
+        }
+    }
+    lines.push("Backpack:".to_string());
+    for (name, backpack) in (&names, &backpacks).join(){
+        if backpack.owner == *player_entity{
+            lines.push(format!("  {}", name.name));
+        }
+    }
+
+    // only the part of the map the player had seen, cropped to fit it
+    lines.push(String::new());
+    lines.push("Final map:".to_string());
+    let revealed: Vec<usize> = (0..map.tiles.len()).filter(|idx| map.revealed_tiles[*idx]).collect();
+    if !revealed.is_empty(){
+        let xs = revealed.iter().map(|idx| *idx as i32 % map.width);
+        let ys = revealed.iter().map(|idx| *idx as i32 / map.width);
+        let (min_x, max_x) = (xs.clone().min().unwrap(), xs.max().unwrap());
+        let (min_y, max_y) = (ys.clone().min().unwrap(), ys.max().unwrap());
+
+        for y in min_y..=max_y{
+            let row: String = (min_x..=max_x).map(|x| {
+                let idx = map.xy_idx(x, y);
+                if x == player_position.x && y == player_position.y{
+                    '@'
+                } else if map.revealed_tiles[idx]{
+                    tile_char(map.tiles[idx])
+                } else {
+                    ' '
+                }
+            }).collect();
+            lines.push(row.trim_end().to_string());
+        }
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let file_name = format!("./morgue-{}.txt", timestamp);
+
+    let mut file = File::create(&file_name)?;
+    file.write_all(lines.join("\n").as_bytes())?;
+
+    Ok(file_name)
+}
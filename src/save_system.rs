@@ -22,9 +22,11 @@ macro_rules! serialize_components {
 
 pub fn save_game(world: &mut World){
     let map_copy = world.get_mut::<Map>().unwrap().clone();
+    let run_stats_copy = world.get_mut::<RunStats>().unwrap().clone();
+    let visited_levels_copy = world.get_mut::<VisitedLevels>().unwrap().clone();
 
     let save_helper = world.create_entity()
-        .with(SerializationHelper{ map: map_copy })
+        .with(SerializationHelper{ map: map_copy, run_stats: run_stats_copy, visited_levels: visited_levels_copy })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
@@ -34,7 +36,7 @@ pub fn save_game(world: &mut World){
         let mut serializer = serde_json::Serializer::new(file_writer);
 
         serialize_components!(world, serializer, data, 
-            Position, Renderable, Player, FOV, Monster, Name, BlocksTile, CombatStats, WantsToMelee, SuffersDamage, Item, ProvidesHealing, InBackpack, WantsToPickUpItem, WantsToUseItem, WantsToDropItem, Consumable, Ranged, InflictsDamage, AreaOfEffect, CausesConfusion, IsConfused, GivesMovementSpeed, HasMovementSpeedModifier, SerializationHelper, Equippable, Equipped, MeleePowerBonus, DefenseBonus, WantsToUnequipItem);
+            Position, OtherLevelPosition, Renderable, Player, FOV, Monster, Name, BlocksTile, CombatStats, WantsToMelee, Item, ProvidesHealing, InBackpack, WantsToPickUpItem, WantsToUseItem, WantsToDropItem, Consumable, Ranged, InflictsDamage, AreaOfEffect, CausesConfusion, IsConfused, GivesMovementSpeed, HasMovementSpeedModifier, SerializationHelper, Equippable, Equipped, MeleePowerBonus, DefenseBonus, WantsToUnequipItem, Gold, Wallet, Shopkeeper, WantsToBuyItem, WantsToSellItem, LootTable, Hidden, EntryTrigger, EntityMoved, Teleports, SoundsAlarm, Alerted, Key, StartsFire, CreatesGas, Burning, Artifact, UsesAbility, SummonsMinions, Potion, WantsToThrowItem, Charges, RechargesOverTime, RechargesItems, Mana, Spell, KnownSpell, TeachesSpell, WantsToCastSpell, MeleeDamageType, Resistant, Vulnerable, Thorns, Grudge);
     }
 
 
@@ -85,7 +87,7 @@ pub fn load_game(world: &mut World){
         );
 
         deserialize_components!(world, deserializer, data,
-            Position, Renderable, Player, FOV, Monster, Name, BlocksTile, CombatStats, WantsToMelee, SuffersDamage, Item, ProvidesHealing, InBackpack, WantsToPickUpItem, WantsToUseItem, WantsToDropItem, Consumable, Ranged, InflictsDamage, AreaOfEffect, CausesConfusion, IsConfused, GivesMovementSpeed, HasMovementSpeedModifier, SerializationHelper, Equippable, Equipped, MeleePowerBonus, DefenseBonus, WantsToUnequipItem);
+            Position, OtherLevelPosition, Renderable, Player, FOV, Monster, Name, BlocksTile, CombatStats, WantsToMelee, Item, ProvidesHealing, InBackpack, WantsToPickUpItem, WantsToUseItem, WantsToDropItem, Consumable, Ranged, InflictsDamage, AreaOfEffect, CausesConfusion, IsConfused, GivesMovementSpeed, HasMovementSpeedModifier, SerializationHelper, Equippable, Equipped, MeleePowerBonus, DefenseBonus, WantsToUnequipItem, Gold, Wallet, Shopkeeper, WantsToBuyItem, WantsToSellItem, LootTable, Hidden, EntryTrigger, EntityMoved, Teleports, SoundsAlarm, Alerted, Key, StartsFire, CreatesGas, Burning, Artifact, UsesAbility, SummonsMinions, Potion, WantsToThrowItem, Charges, RechargesOverTime, RechargesItems, Mana, Spell, KnownSpell, TeachesSpell, WantsToCastSpell, MeleeDamageType, Resistant, Vulnerable, Thorns, Grudge);
 
     }
 
@@ -101,6 +103,12 @@ pub fn load_game(world: &mut World){
             *map = serialization_helper.map.clone();
             map.load_from_save();
 
+            let mut run_stats = world.write_resource::<RunStats>();
+            *run_stats = serialization_helper.run_stats.clone();
+            world.write_resource::<GameLog>().turn = run_stats.turns;
+
+            *world.write_resource::<VisitedLevels>() = serialization_helper.visited_levels.clone();
+
             serialization_helper_to_delete = Some(entity);
         }
 
@@ -0,0 +1,133 @@
+use specs::prelude::*;
+use rltk::RGB;
+
+use crate::{GameLog, LogEntry, Name, InBackpack, Wallet, WantsToBuyItem, WantsToSellItem, ProvidesHealing, InflictsDamage, AreaOfEffect, CausesConfusion, GivesMovementSpeed, MeleePowerBonus, DefenseBonus, Consumable, StartsFire, CreatesGas, Charges, RechargesOverTime, Resistant, Thorns};
+
+pub struct ShopSystem{ }
+
+impl<'a> System<'a> for ShopSystem{
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToBuyItem>,
+                        WriteStorage<'a, WantsToSellItem>,
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Wallet>,
+                        ReadStorage<'a, Name>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut gamelog, entities, mut wants_to_buy, mut wants_to_sell, mut backpack, mut wallets, names) = data;
+
+        for (entity, buy) in (&entities, &wants_to_buy).join(){
+            let can_afford = wallets.get(entity).is_some_and(|wallet| wallet.gold >= buy.price);
+            let item_name = names.get(buy.item).map_or("item".to_string(), |name| name.name.clone());
+
+            if !can_afford{
+                if entity == *player_entity{
+                    gamelog.add(LogEntry::new().text("You can't afford the ").name(&item_name).text("."));
+                }
+                continue;
+            }
+
+            if let Some(wallet) = wallets.get_mut(entity){
+                wallet.gold -= buy.price;
+            }
+            if let Some(vendor_wallet) = wallets.get_mut(buy.vendor){
+                vendor_wallet.gold += buy.price;
+            }
+
+            backpack.insert(buy.item, InBackpack { owner: entity })
+                .expect("Could not move bought item into backpack.");
+
+            if entity == *player_entity{
+                gamelog.add(LogEntry::new().text("You buy the ").name(&item_name).text(" for ").colour(RGB::named(rltk::GOLD), buy.price).text(" gold."));
+            }
+        }
+
+        for (entity, sell) in (&entities, &wants_to_sell).join(){
+            let item_name = names.get(sell.item).map_or("item".to_string(), |name| name.name.clone());
+            let vendor_can_afford = wallets.get(sell.vendor).is_some_and(|wallet| wallet.gold >= sell.price);
+
+            if !vendor_can_afford{
+                if entity == *player_entity{
+                    gamelog.add(LogEntry::new().text("The shopkeeper can't afford your ").name(&item_name).text("."));
+                }
+                continue;
+            }
+
+            if let Some(vendor_wallet) = wallets.get_mut(sell.vendor){
+                vendor_wallet.gold -= sell.price;
+            }
+            if let Some(wallet) = wallets.get_mut(entity){
+                wallet.gold += sell.price;
+            }
+
+            backpack.insert(sell.item, InBackpack { owner: sell.vendor })
+                .expect("Could not move sold item into vendor's backpack.");
+
+            if entity == *player_entity{
+                gamelog.add(LogEntry::new().text("You sell the ").name(&item_name).text(" for ").colour(RGB::named(rltk::GOLD), sell.price).text(" gold."));
+            }
+        }
+
+        wants_to_buy.clear();
+        wants_to_sell.clear();
+    }
+}
+
+/// Price a vendor asks for an item, derived from what the item does.
+/// Consumables are worth their effect for every charge left
+pub fn item_price(world: &World, item: Entity) -> i32{
+    let mut value = 5;
+
+    if let Some(healing) = world.read_storage::<ProvidesHealing>().get(item){
+        value += healing.heal_amount * 2;
+    }
+    if let Some(damage) = world.read_storage::<InflictsDamage>().get(item){
+        value += damage.damage * 2;
+    }
+    if let Some(aoe) = world.read_storage::<AreaOfEffect>().get(item){
+        value += aoe.radius * 5;
+    }
+    if let Some(confusion) = world.read_storage::<CausesConfusion>().get(item){
+        value += confusion.turns * 5;
+    }
+    if let Some(speed) = world.read_storage::<GivesMovementSpeed>().get(item){
+        value += speed.speed_modifier * speed.turns * 2;
+    }
+    if let Some(fire) = world.read_storage::<StartsFire>().get(item){
+        value += fire.turns * 2;
+    }
+    if let Some(gas) = world.read_storage::<CreatesGas>().get(item){
+        value += gas.density * 2;
+    }
+    if let Some(power) = world.read_storage::<MeleePowerBonus>().get(item){
+        value += power.power * 10;
+    }
+    if let Some(defense) = world.read_storage::<DefenseBonus>().get(item){
+        value += defense.defense * 10;
+    }
+    if let Some(resistant) = world.read_storage::<Resistant>().get(item){
+        value += (resistant.resisted.len() as i32 + resistant.immune.len() as i32 * 2) * 15;
+    }
+    if let Some(thorns) = world.read_storage::<Thorns>().get(item){
+        value += thorns.damage * 10;
+    }
+    if let Some(consumable) = world.read_storage::<Consumable>().get(item){
+        value *= i32::max(1, consumable.charges);
+    }
+    if let Some(charges) = world.read_storage::<Charges>().get(item){
+        value *= i32::max(1, charges.current);
+    }
+    if world.read_storage::<RechargesOverTime>().get(item).is_some(){
+        value += 30;
+    }
+
+    value
+}
+
+/// Vendors pay half of what they would ask for an item
+pub fn item_sell_price(world: &World, item: Entity) -> i32{
+    i32::max(1, item_price(world, item) / 2)
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::{ProvidesHealing, Consumable, Ranged, InflictsDamage, AreaOfEffect, CausesConfusion, GivesMovementSpeed, SerializeMe, RandomTable, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus};
+use crate::{ProvidesHealing, Consumable, Ranged, InflictsDamage, AreaOfEffect, CausesConfusion, GivesMovementSpeed, SerializeMe, RandomTable, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus, Gold, Wallet, Shopkeeper, InBackpack, Map, LootTable, Hidden, EntryTrigger, Teleports, SoundsAlarm, Key, TileType, StartsFire, CreatesGas, LevelTheme, Artifact, UsesAbility, SummonsMinions, Potion, Charges, RechargesOverTime, RechargesItems, Mana, Spell, KnownSpell, TeachesSpell, DamageType, MeleeDamageType, Resistant, Vulnerable, Thorns};
 
 use super::{Player, Position, Renderable, FOV, Name, CombatStats, Monster, BlocksTile, Rect, Item};
 use specs::{prelude::*, saveload::{MarkedBuilder, SimpleMarker}};
@@ -8,6 +8,7 @@ use rltk::{RGB, RandomNumberGenerator};
 
 pub const MAX_MONSTERS_PER_ROOM: i32 = 4;
 pub const MAX_ITEMS_PER_ROOM: i32 = 2;
+pub const SHOP_STOCK_SIZE: i32 = 5;
 
 pub fn player(world: &mut World, player_x: i32, player_y: i32) -> Entity {
     world.create_entity()
@@ -22,11 +23,13 @@ pub fn player(world: &mut World, player_x: i32, player_y: i32) -> Entity {
         .with(FOV{ visible_tiles: Vec::new(), range: 8, needs_update: true, })
         .with(Name{ name: "Player".to_string() })
         .with(CombatStats{ max_hp: 30, hp: 30, attack: 5, defense: 2, })
+        .with(Wallet{ gold: 0 })
+        .with(Mana{ current: 10, max: 10, regen_turns: 4, regen_timer: 0 })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
-pub fn health_potion(world: &mut World, x: i32, y: i32){
+pub fn health_potion(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -39,11 +42,12 @@ pub fn health_potion(world: &mut World, x: i32, y: i32){
         .with(Item{ })
         .with(ProvidesHealing{ heal_amount: 8 })
         .with(Consumable{ charges: 3 })
+        .with(Potion{ })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-pub fn magic_missile_scroll(world: &mut World, x: i32, y: i32){
+pub fn magic_missile_scroll(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -56,12 +60,12 @@ pub fn magic_missile_scroll(world: &mut World, x: i32, y: i32){
         .with(Item{ })
         .with(Consumable{ charges: 1 })
         .with(Ranged{ range: 6 })
-        .with(InflictsDamage{ damage: 8 })
+        .with(InflictsDamage{ damage: 8, damage_type: DamageType::Psychic })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-pub fn fireball_spell(world: &mut World, x: i32, y: i32){
+pub fn fireball_spell(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -74,13 +78,14 @@ pub fn fireball_spell(world: &mut World, x: i32, y: i32){
         .with(Item{ })
         .with(Consumable{ charges: 1 })
         .with(Ranged{ range: 6 })
-        .with(InflictsDamage{ damage: 20 })
+        .with(InflictsDamage{ damage: 20, damage_type: DamageType::Fire })
         .with(AreaOfEffect{ radius: 3 })
+        .with(StartsFire{ turns: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-pub fn confusion_spell(world: &mut World, x: i32, y: i32){
+pub fn confusion_spell(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -95,10 +100,168 @@ pub fn confusion_spell(world: &mut World, x: i32, y: i32){
         .with(Ranged{ range: 6 })
         .with(CausesConfusion{ turns: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+pub fn poison_gas_scroll(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437(')'),
+            foreground: RGB::named(rltk::GREEN),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Poison Gas Scroll".to_string() })
+        .with(Item{ })
+        .with(Consumable{ charges: 1 })
+        .with(Ranged{ range: 6 })
+        .with(AreaOfEffect{ radius: 1 })
+        .with(CreatesGas{ density: 8 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn wand_of_magic_missile(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('-'),
+            foreground: RGB::named(rltk::CYAN),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Wand of Magic Missile".to_string() })
+        .with(Item{ })
+        .with(Charges{ current: 4, max: 4 })
+        .with(Ranged{ range: 6 })
+        .with(InflictsDamage{ damage: 6, damage_type: DamageType::Psychic })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn wand_of_frost(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('-'),
+            foreground: RGB::named(rltk::LIGHT_BLUE),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Wand of Frost".to_string() })
+        .with(Item{ })
+        .with(Charges{ current: 3, max: 3 })
+        .with(Ranged{ range: 6 })
+        .with(InflictsDamage{ damage: 8, damage_type: DamageType::Cold })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn wand_of_confusion(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('-'),
+            foreground: RGB::named(rltk::PINK),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Wand of Confusion".to_string() })
+        .with(Item{ })
+        .with(Charges{ current: 3, max: 3 })
+        .with(Ranged{ range: 6 })
+        .with(CausesConfusion{ turns: 3 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Few charges but they come back on their own
+pub fn staff_of_fire(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('_'),
+            foreground: RGB::named(rltk::ORANGE),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Staff of Fire".to_string() })
+        .with(Item{ })
+        .with(Charges{ current: 2, max: 2 })
+        .with(RechargesOverTime{ turns: 50, timer: 0 })
+        .with(Ranged{ range: 6 })
+        .with(InflictsDamage{ damage: 10, damage_type: DamageType::Fire })
+        .with(AreaOfEffect{ radius: 1 })
+        .with(StartsFire{ turns: 3 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn recharging_scroll(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437(')'),
+            foreground: RGB::named(rltk::LIGHT_BLUE),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Recharging Scroll".to_string() })
+        .with(Item{ })
+        .with(Consumable{ charges: 1 })
+        .with(RechargesItems{ })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn spellbook(world: &mut World, x: i32, y: i32, spell: &str) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('¶'),
+            foreground: RGB::named(rltk::VIOLET),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: format!("Spellbook of {}", spell) })
+        .with(Item{ })
+        .with(Consumable{ charges: 1 })
+        .with(TeachesSpell{ spell: spell.to_string() })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Give owner a spell. Spells use the same effect components as the scrolls they mirror
+pub fn learn_spell(world: &mut World, spell: &str, owner: Entity) -> Option<Entity>{
+    let builder = world.create_entity()
+        .with(Name{ name: spell.to_string() })
+        .with(KnownSpell{ owner });
+
+    let builder = match spell{
+        "Magic Missile" => builder
+            .with(Spell{ mana_cost: 3 })
+            .with(Ranged{ range: 6 })
+            .with(InflictsDamage{ damage: 6, damage_type: DamageType::Psychic }),
+        "Fireball" => builder
+            .with(Spell{ mana_cost: 8 })
+            .with(Ranged{ range: 6 })
+            .with(InflictsDamage{ damage: 12, damage_type: DamageType::Fire })
+            .with(AreaOfEffect{ radius: 2 }),
+        "Confusion" => builder
+            .with(Spell{ mana_cost: 4 })
+            .with(Ranged{ range: 6 })
+            .with(CausesConfusion{ turns: 3 }),
+        "Heal" => builder
+            .with(Spell{ mana_cost: 5 })
+            .with(ProvidesHealing{ heal_amount: 8 }),
+        _ => return None,
+    };
+
+    Some(builder.marked::<SimpleMarker<SerializeMe>>().build())
 }
 
-pub fn dash_boots(world: &mut World, x: i32, y: i32){
+pub fn dash_boots(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -112,13 +275,14 @@ pub fn dash_boots(world: &mut World, x: i32, y: i32){
         .with(Consumable{ charges: 1 })
         .with(GivesMovementSpeed{ speed_modifier: 2, turns: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
 // TODO: Refactor and extract duplicated logic
 // monster and item spawning is the exact same
-pub fn spawn_entities_in_room(world: &mut World, room: &Rect, map_depth: i32){
-    let spawn_table = room_table(map_depth);
+pub fn spawn_entities_in_room(world: &mut World, map: &Map, room: &Rect){
+    let map_depth = map.depth;
+    let spawn_table = room_table(map.theme, map_depth);
     let mut spawn_points: HashMap<(i32, i32), String> = HashMap::new();
 
     {    
@@ -133,7 +297,10 @@ pub fn spawn_entities_in_room(world: &mut World, room: &Rect, map_depth: i32){
                 let x = room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1));
                 let y = room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1));
 
-                if !spawn_points.contains_key(&(x, y)){
+                // nothing spawns in water or lava
+                let is_spawnable_tile = matches!(map.tiles[map.xy_idx(x, y)], TileType::Floor | TileType::Grass | TileType::TallGrass);
+
+                if is_spawnable_tile && !spawn_points.contains_key(&(x, y)){
                     spawn_points.insert((x, y), spawn_table.roll(&mut rng));
                     added = true;
                 } else {
@@ -145,33 +312,221 @@ pub fn spawn_entities_in_room(world: &mut World, room: &Rect, map_depth: i32){
     }
 
     for ((x, y), name) in spawn_points.iter(){
-        match name.as_ref(){
-            "Goblin" => goblin(world, *x, *y),
-            "Orc" => orc(world, *x, *y),
-            "Health Potion" => health_potion(world, *x, *y),
-            "Fireball Scroll" => fireball_spell(world, *x, *y),
-            "Confusion Scroll" => confusion_spell(world, *x, *y),
-            "Magic Missile Scroll" => magic_missile_scroll(world, *x, *y),
-            "Dagger" => dagger(world, *x, *y),
-            "Shield" => shield(world, *x, *y),
-            "Tower Shield" => tower_shield(world, *x, *y),
-            "Longsword" => longsword(world, *x, *y),
-            _ => {},
-        }
+        spawn_named_entity(world, name, *x, *y, map_depth);
     }
 
     
 }
 
-pub fn orc(world: &mut World, x: i32, y: i32){
-    monster(world, x, y, rltk::to_cp437('g'), "Goblin");
+/// Spawn whatever a random table rolled by its name. Returns None for names nothing is registered under
+pub fn spawn_named_entity(world: &mut World, name: &str, x: i32, y: i32, map_depth: i32) -> Option<Entity>{
+    let entity = match name{
+        "Goblin" => goblin(world, x, y),
+        "Orc" => orc(world, x, y),
+        "Skeleton" => skeleton(world, x, y),
+        "Zombie" => zombie(world, x, y),
+        "Giant Spider" => giant_spider(world, x, y),
+        "Fire Elemental" => fire_elemental(world, x, y),
+        "Wolf" => wolf(world, x, y),
+        "Orc Captain" => orc_captain(world, x, y),
+        "Infernal Warlord" => infernal_warlord(world, x, y),
+        "Ember Crown" => ember_crown(world, x, y),
+        "Health Potion" => health_potion(world, x, y),
+        "Fireball Scroll" => fireball_spell(world, x, y),
+        "Confusion Scroll" => confusion_spell(world, x, y),
+        "Magic Missile Scroll" => magic_missile_scroll(world, x, y),
+        "Wand of Magic Missile" => wand_of_magic_missile(world, x, y),
+        "Wand of Confusion" => wand_of_confusion(world, x, y),
+        "Wand of Frost" => wand_of_frost(world, x, y),
+        "Staff of Fire" => staff_of_fire(world, x, y),
+        "Recharging Scroll" => recharging_scroll(world, x, y),
+        "Spellbook of Magic Missile" => spellbook(world, x, y, "Magic Missile"),
+        "Spellbook of Fireball" => spellbook(world, x, y, "Fireball"),
+        "Spellbook of Confusion" => spellbook(world, x, y, "Confusion"),
+        "Spellbook of Heal" => spellbook(world, x, y, "Heal"),
+        "Dagger" => dagger(world, x, y),
+        "Mace" => mace(world, x, y),
+        "Shield" => shield(world, x, y),
+        "Tower Shield" => tower_shield(world, x, y),
+        "Salamander Shield" => salamander_shield(world, x, y),
+        "Spiked Shield" => spiked_shield(world, x, y),
+        "Longsword" => longsword(world, x, y),
+        "Spike Trap" => spike_trap(world, x, y),
+        "Teleport Trap" => teleport_trap(world, x, y),
+        "Alarm Trap" => alarm_trap(world, x, y),
+        "Gas Trap" => gas_trap(world, x, y),
+        "Poison Gas Scroll" => poison_gas_scroll(world, x, y),
+        "Key" => key(world, x, y),
+        "Shopkeeper" => shopkeeper(world, x, y, map_depth),
+        "Gold" => {
+            let amount = world.write_resource::<RandomNumberGenerator>().roll_dice(map_depth, 6);
+            gold_pile(world, x, y, amount)
+        },
+        _ => return None,
+    };
+
+    Some(entity)
+}
+
+/// Spawn whatever the level's prefabs asked for and the contents of every room except the first,
+/// which is where the player starts. Some levels also get a shopkeeper tucked into the corner of a room
+pub fn populate_level(world: &mut World, map: &Map){
+    for (idx, name) in map.spawn_list.iter(){
+        spawn_named_entity(world, name, *idx as i32 % map.width, *idx as i32 / map.width, map.depth);
+    }
+
+    for room in map.rooms.iter().skip(1){
+        spawn_entities_in_room(world, map, room);
+    }
+
+    spawn_keys_for_locked_doors(world, map);
+
+    // the last room holds the stairs so keep the shop out of it
+    if map.rooms.len() < 3{
+        return;
+    }
+
+    let shop_room = {
+        let mut rng = world.write_resource::<RandomNumberGenerator>();
+        if rng.roll_dice(1, 3) != 1{
+            return;
+        }
+        map.rooms[rng.range(1, map.rooms.len() - 1)]
+    };
+
+    // random spawns never land on a room's top row, open levels may have wall there so take its first open tile
+    let shop_x = (shop_room.x1..=shop_room.x2)
+        .find(|x| matches!(map.tiles[map.xy_idx(*x, shop_room.y1)], TileType::Floor | TileType::Grass));
+    if let Some(shop_x) = shop_x{
+        shopkeeper(world, shop_x, shop_room.y1, map.depth);
+    }
+}
+
+/// Drop one key per locked door somewhere the player can walk to without opening any locked door,
+/// so a key can never end up locked behind the door it opens
+fn spawn_keys_for_locked_doors(world: &mut World, map: &Map){
+    let locked_doors = map.tiles.iter()
+        .filter(|tile| **tile == TileType::LockedDoor)
+        .count();
+    if locked_doors == 0{
+        return;
+    }
+
+    let (start_x, start_y) = map.starting_position;
+    let reachable_floor: Vec<usize> = map.reachable_tiles(map.xy_idx(start_x, start_y))
+        .into_iter()
+        .filter(|idx| map.tiles[*idx] == TileType::Floor)
+        .collect();
+
+    for _ in 0..locked_doors{
+        let idx = {
+            let mut rng = world.write_resource::<RandomNumberGenerator>();
+            reachable_floor[rng.range(0, reachable_floor.len())]
+        };
+        key(world, idx as i32 % map.width, idx as i32 / map.width);
+    }
+}
+
+pub fn orc(world: &mut World, x: i32, y: i32) -> Entity{
+    monster(world, x, y, rltk::to_cp437('o'), "Orc", CombatStats{ max_hp: 16, hp: 16, attack: 4, defense: 1, })
+}
+
+pub fn goblin(world: &mut World, x: i32, y: i32) -> Entity{
+    monster(world, x, y, rltk::to_cp437('g'), "Goblin", CombatStats{ max_hp: 16, hp: 16, attack: 4, defense: 1, })
+}
+
+/// Brittle bones shatter under blunt blows, but there is no flesh to poison and no mind to hurt
+pub fn skeleton(world: &mut World, x: i32, y: i32) -> Entity{
+    let skeleton = monster(world, x, y, rltk::to_cp437('s'), "Skeleton", CombatStats{ max_hp: 14, hp: 14, attack: 4, defense: 1, });
+    damage_traits(world, skeleton, None, &[DamageType::Cold], &[DamageType::Poison, DamageType::Psychic], &[DamageType::Bludgeoning]);
+    skeleton
 }
 
-pub fn goblin(world: &mut World, x: i32, y: i32){
-    monster(world, x, y, rltk::to_cp437('o'), "Orc");
+pub fn zombie(world: &mut World, x: i32, y: i32) -> Entity{
+    let zombie = monster(world, x, y, rltk::to_cp437('z'), "Zombie", CombatStats{ max_hp: 22, hp: 22, attack: 3, defense: 0, });
+    damage_traits(world, zombie, None, &[], &[DamageType::Poison, DamageType::Psychic], &[DamageType::Fire]);
+    zombie
 }
 
-pub fn monster<S: ToString> (world: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S) -> Entity{
+pub fn giant_spider(world: &mut World, x: i32, y: i32) -> Entity{
+    let spider = monster(world, x, y, rltk::to_cp437('x'), "Giant Spider", CombatStats{ max_hp: 18, hp: 18, attack: 5, defense: 1, });
+    damage_traits(world, spider, Some(DamageType::Poison), &[DamageType::Poison], &[], &[]);
+    spider
+}
+
+pub fn fire_elemental(world: &mut World, x: i32, y: i32) -> Entity{
+    let elemental = monster(world, x, y, rltk::to_cp437('E'), "Fire Elemental", CombatStats{ max_hp: 24, hp: 24, attack: 7, defense: 2, });
+    world.write_storage::<Renderable>().insert(elemental, Renderable{
+        symbol: rltk::to_cp437('E'),
+        foreground: RGB::named(rltk::ORANGE),
+        background: RGB::named(rltk::BLACK),
+        render_order: 1,
+    }).expect("Could not colour the fire elemental.");
+    damage_traits(world, elemental, Some(DamageType::Fire), &[], &[DamageType::Fire, DamageType::Poison], &[DamageType::Cold]);
+    elemental
+}
+
+/// What kind of damage a creature deals and what it shrugs off or fears
+fn damage_traits(world: &mut World, creature: Entity, melee: Option<DamageType>, resisted: &[DamageType], immune: &[DamageType], vulnerable: &[DamageType]){
+    if let Some(damage_type) = melee{
+        world.write_storage::<MeleeDamageType>().insert(creature, MeleeDamageType{ damage_type })
+            .expect("Could not set melee damage type.");
+    }
+    if !resisted.is_empty() || !immune.is_empty(){
+        world.write_storage::<Resistant>().insert(creature, Resistant{ resisted: resisted.to_vec(), immune: immune.to_vec() })
+            .expect("Could not add resistances.");
+    }
+    if !vulnerable.is_empty(){
+        world.write_storage::<Vulnerable>().insert(creature, Vulnerable{ damage_types: vulnerable.to_vec() })
+            .expect("Could not add vulnerabilities.");
+    }
+}
+
+pub fn wolf(world: &mut World, x: i32, y: i32) -> Entity{
+    monster(world, x, y, rltk::to_cp437('w'), "Wolf", CombatStats{ max_hp: 20, hp: 20, attack: 6, defense: 1, })
+}
+
+pub fn orc_captain(world: &mut World, x: i32, y: i32) -> Entity{
+    monster(world, x, y, rltk::to_cp437('O'), "Orc Captain", CombatStats{ max_hp: 30, hp: 30, attack: 8, defense: 3, })
+}
+
+/// The boss waiting at the bottom of the dungeon. Breathes fire, calls in orcs and carries the artifact
+pub fn infernal_warlord(world: &mut World, x: i32, y: i32) -> Entity{
+    let fire_breath = world.create_entity()
+        .with(Name{ name: "Fire Breath".to_string() })
+        .with(InflictsDamage{ damage: 8, damage_type: DamageType::Fire })
+        .with(AreaOfEffect{ radius: 1 })
+        .with(StartsFire{ turns: 3 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    let crown = ember_crown(world, x, y);
+    world.write_storage::<Position>().remove(crown);
+
+    let warlord = monster(world, x, y, rltk::to_cp437('&'), "Infernal Warlord", CombatStats{ max_hp: 80, hp: 80, attack: 10, defense: 4, });
+
+    world.write_storage::<Renderable>().insert(warlord, Renderable{
+        symbol: rltk::to_cp437('&'),
+        foreground: RGB::named(rltk::MAGENTA),
+        background: RGB::named(rltk::BLACK),
+        render_order: 1,
+    }).expect("Could not colour the warlord.");
+    world.write_storage::<UsesAbility>().insert(warlord, UsesAbility{ ability: fire_breath, range: 6, cooldown: 4, timer: 0 })
+        .expect("Could not give the warlord its fire breath.");
+    damage_traits(world, warlord, Some(DamageType::Fire), &[], &[DamageType::Fire], &[DamageType::Cold]);
+    world.write_storage::<SummonsMinions>().insert(warlord, SummonsMinions{ minion: "Orc".to_string(), count: 2, cooldown: 10, timer: 5 })
+        .expect("Could not let the warlord summon minions.");
+    world.write_storage::<Wallet>().insert(warlord, Wallet{ gold: 100 })
+        .expect("Could not fill the warlord's wallet.");
+    world.write_storage::<InBackpack>().insert(crown, InBackpack{ owner: warlord })
+        .expect("Could not give the warlord its crown.");
+
+    warlord
+}
+
+pub fn monster<S: ToString> (world: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S, stats: CombatStats) -> Entity{
+    let gold = world.write_resource::<RandomNumberGenerator>().roll_dice(1, 6) - 1;
+
     world.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -184,26 +539,186 @@ pub fn monster<S: ToString> (world: &mut World, x: i32, y: i32, glyph: rltk::Fon
         .with(Monster{ })
         .with(Name{ name: name.to_string() })
         .with(BlocksTile{ })
-        .with(CombatStats{ max_hp: 16, hp: 16, attack: 4, defense: 1, })
+        .with(stats)
+        .with(Wallet{ gold })
+        .with(LootTable{ table: name.to_string() })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
-pub fn room_table(map_depth: i32) -> RandomTable{
+/// What a level's rooms can hold. Each theme brings its own monsters and leans towards its own loot
+pub fn room_table(theme: LevelTheme, map_depth: i32) -> RandomTable{
+    let table = match theme{
+        LevelTheme::Crypt => RandomTable::new()
+            .add("Skeleton", 10)
+            .add("Zombie", 2 + map_depth)
+            .add("Confusion Scroll", 4 + map_depth)
+            .add("Wand of Confusion", map_depth)
+            .add("Spellbook of Confusion", map_depth - 1)
+            .add("Mace", 1 + map_depth)
+            .add("Alarm Trap", map_depth),
+        LevelTheme::Caves => RandomTable::new()
+            .add("Goblin", 10)
+            .add("Giant Spider", map_depth)
+            .add("Poison Gas Scroll", 2 + map_depth)
+            .add("Gas Trap", map_depth),
+        LevelTheme::Forest => RandomTable::new()
+            .add("Wolf", 8)
+            .add("Goblin", 4)
+            .add("Fireball Scroll", 4 + map_depth)
+            .add("Staff of Fire", map_depth - 3)
+            .add("Spellbook of Fireball", map_depth - 4)
+            .add("Wand of Frost", map_depth - 4)
+            .add("Health Potion", 3),
+        LevelTheme::Fortress => RandomTable::new()
+            .add("Orc", 10)
+            .add("Orc Captain", map_depth - 5)
+            .add("Fire Elemental", map_depth - 5)
+            .add("Longsword", map_depth - 1)
+            .add("Tower Shield", map_depth - 1)
+            .add("Salamander Shield", map_depth - 4)
+            .add("Spiked Shield", map_depth - 3)
+            .add("Wand of Frost", map_depth - 5)
+            .add("Alarm Trap", map_depth),
+        LevelTheme::Town | LevelTheme::Lair => RandomTable::new()
+            .add("Goblin", 10)
+            .add("Orc", 1 + map_depth),
+    };
+
+    table
+        .add("Health Potion", 7)
+        .add("Fireball Scroll", 2 + map_depth)
+        .add("Confusion Scroll", 2 + map_depth)
+        .add("Magic Missile Scroll", 4)
+        .add("Wand of Magic Missile", map_depth)
+        .add("Recharging Scroll", map_depth)
+        .add("Spellbook of Magic Missile", 1 + map_depth)
+        .add("Spellbook of Heal", map_depth)
+        .add("Dagger", 3)
+        .add("Shield", 3)
+        .add("Gold", 5)
+        .add("Spike Trap", 2 + map_depth)
+        .add("Teleport Trap", map_depth - 1)
+}
+
+/// What a monster may drop when it dies, keyed by the monster's name.
+/// Rolling "None" means the monster drops nothing extra
+pub fn loot_table(monster_name: &str, map_depth: i32) -> RandomTable{
+    match monster_name{
+        "Goblin" => RandomTable::new()
+            .add("None", 8)
+            .add("Gold", 6)
+            .add("Health Potion", 2),
+        "Orc" => RandomTable::new()
+            .add("None", 8)
+            .add("Dagger", 3)
+            .add("Shield", 2)
+            .add("Longsword", map_depth)
+            .add("Tower Shield", map_depth - 1),
+        "Skeleton" => RandomTable::new()
+            .add("None", 8)
+            .add("Dagger", 3)
+            .add("Shield", 3),
+        "Zombie" => RandomTable::new()
+            .add("None", 8)
+            .add("Health Potion", 3),
+        "Giant Spider" => RandomTable::new()
+            .add("None", 8)
+            .add("Poison Gas Scroll", 2),
+        "Orc Captain" => RandomTable::new()
+            .add("Gold", 4)
+            .add("Longsword", 4)
+            .add("Tower Shield", 4),
+        _ => RandomTable::new(),
+    }
+}
+
+/// What a shopkeeper can stock, no monsters or gold
+pub fn shop_table(map_depth: i32) -> RandomTable{
     RandomTable::new()
-        .add("Goblin", 10)
-        .add("Orc", 1 + map_depth)
         .add("Health Potion", 7)
         .add("Fireball Scroll", 2 + map_depth)
         .add("Confusion Scroll", 2 + map_depth)
         .add("Magic Missile Scroll", 4)
+        .add("Poison Gas Scroll", map_depth)
+        .add("Wand of Magic Missile", map_depth)
+        .add("Wand of Confusion", map_depth - 1)
+        .add("Staff of Fire", map_depth - 4)
+        .add("Wand of Frost", map_depth - 3)
+        .add("Recharging Scroll", 1 + map_depth)
+        .add("Spellbook of Magic Missile", 2)
+        .add("Spellbook of Heal", map_depth)
+        .add("Spellbook of Fireball", map_depth - 3)
         .add("Dagger", 3)
+        .add("Mace", 2)
         .add("Shield", 3)
-        .add("Tower Shield", map_depth-1)
-        .add("Longsword", map_depth-1)
+        .add("Tower Shield", map_depth)
+        .add("Longsword", map_depth)
+}
+
+pub fn shopkeeper(world: &mut World, x: i32, y: i32, map_depth: i32) -> Entity{
+    let vendor = world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('@'),
+            foreground: RGB::named(rltk::GOLD),
+            background: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Name{ name: "Shopkeeper".to_string() })
+        .with(Shopkeeper{ })
+        .with(BlocksTile{ })
+        .with(Wallet{ gold: 50 * (map_depth + 1) })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    let stock_table = shop_table(map_depth);
+    for _ in 0..SHOP_STOCK_SIZE{
+        let item_name = stock_table.roll(&mut world.write_resource::<RandomNumberGenerator>());
+
+        // items are spawned on the shopkeeper's tile then moved straight into their backpack
+        if let Some(item) = spawn_named_entity(world, &item_name, x, y, map_depth){
+            world.write_storage::<Position>().remove(item);
+            world.write_storage::<InBackpack>().insert(item, InBackpack { owner: vendor })
+                .expect("Could not stock shopkeeper.");
+        }
+    }
+
+    vendor
+}
+
+pub fn corpse(world: &mut World, x: i32, y: i32, monster_name: &str) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('%'),
+            foreground: RGB::named(rltk::DARK_RED),
+            background: RGB::named(rltk::BLACK),
+            render_order: 3,
+        })
+        .with(Name{ name: format!("{} corpse", monster_name) })
+        .with(Item{ })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-pub fn dagger(world: &mut World, x: i32, y: i32){
+pub fn gold_pile(world: &mut World, x: i32, y: i32, amount: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('$'),
+            foreground: RGB::named(rltk::GOLD),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: format!("{} gold", amount) })
+        .with(Item{ })
+        .with(Gold{ amount })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn dagger(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -217,10 +732,28 @@ pub fn dagger(world: &mut World, x: i32, y: i32){
         .with(Equippable{ slot: EquipmentSlot::Melee })
         .with(MeleePowerBonus{ power: 2 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+pub fn mace(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('/'),
+            foreground: RGB::named(rltk::LIGHT_GRAY),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Mace".to_string() })
+        .with(Item{ })
+        .with(Equippable{ slot: EquipmentSlot::Melee })
+        .with(MeleePowerBonus{ power: 3 })
+        .with(MeleeDamageType{ damage_type: DamageType::Bludgeoning })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-pub fn shield(world: &mut World, x:i32, y: i32){
+pub fn shield(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -234,10 +767,46 @@ pub fn shield(world: &mut World, x:i32, y: i32){
         .with(Equippable{ slot: EquipmentSlot::Shield })
         .with(DefenseBonus{ defense: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-pub fn longsword(world: &mut World, x: i32, y: i32){
+pub fn salamander_shield(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('('),
+            foreground: RGB::named(rltk::ORANGE),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Salamander Shield".to_string() })
+        .with(Item{ })
+        .with(Equippable{ slot: EquipmentSlot::Shield })
+        .with(DefenseBonus{ defense: 1 })
+        .with(Resistant{ resisted: vec![DamageType::Fire], immune: Vec::new() })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn spiked_shield(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('('),
+            foreground: RGB::named(rltk::LIGHT_GRAY),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Spiked Shield".to_string() })
+        .with(Item{ })
+        .with(Equippable{ slot: EquipmentSlot::Shield })
+        .with(DefenseBonus{ defense: 1 })
+        .with(Thorns{ damage: 2 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn longsword(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -251,10 +820,10 @@ pub fn longsword(world: &mut World, x: i32, y: i32){
         .with(Equippable{ slot: EquipmentSlot::Melee })
         .with(MeleePowerBonus{ power: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-pub fn tower_shield(world: &mut World, x: i32, y: i32){
+pub fn tower_shield(world: &mut World, x: i32, y: i32) -> Entity{
     world.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
@@ -268,6 +837,83 @@ pub fn tower_shield(world: &mut World, x: i32, y: i32){
         .with(Equippable{ slot: EquipmentSlot::Shield })
         .with(DefenseBonus{ defense: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
+
+/// The artifact the run is after, carry it back to the town to win
+pub fn ember_crown(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('*'),
+            foreground: RGB::named(rltk::GOLD),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Ember Crown".to_string() })
+        .with(Item{ })
+        .with(Artifact{ })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn key(world: &mut World, x: i32, y: i32) -> Entity{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('k'),
+            foreground: RGB::named(rltk::GOLD),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: "Key".to_string() })
+        .with(Item{ })
+        .with(Key{ })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn trap<'a>(world: &'a mut World, x: i32, y: i32, name: &str) -> EntityBuilder<'a>{
+    world.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable{
+            symbol: rltk::to_cp437('^'),
+            foreground: RGB::named(rltk::RED),
+            background: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name{ name: name.to_string() })
+        .with(Hidden{ })
+        .with(EntryTrigger{ })
+}
+
+pub fn spike_trap(world: &mut World, x: i32, y: i32) -> Entity{
+    trap(world, x, y, "Spike Trap")
+        .with(InflictsDamage{ damage: 6, damage_type: DamageType::Physical })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn teleport_trap(world: &mut World, x: i32, y: i32) -> Entity{
+    trap(world, x, y, "Teleport Trap")
+        .with(Teleports{ })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn alarm_trap(world: &mut World, x: i32, y: i32) -> Entity{
+    trap(world, x, y, "Alarm Trap")
+        .with(SoundsAlarm{ })
+        .with(Consumable{ charges: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn gas_trap(world: &mut World, x: i32, y: i32) -> Entity{
+    trap(world, x, y, "Gas Trap")
+        .with(CreatesGas{ density: 10 })
+        .with(Consumable{ charges: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
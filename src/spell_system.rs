@@ -0,0 +1,111 @@
+use specs::prelude::*;
+use crate::{GameLog, LogEntry, Name, Mana, Spell, KnownSpell, WantsToCastSpell, WantsToUseItem, Ranged, RunState};
+
+/// Spells the owner knows, in the order the cast menu and the number keys use
+pub fn known_spells(world: &World, owner: Entity) -> Vec<Entity>{
+    let entities = world.entities();
+    let known = world.read_storage::<KnownSpell>();
+
+    (&entities, &known).join()
+        .filter(|(_spell, known)| known.owner == owner)
+        .map(|(spell, _known)| spell)
+        .collect()
+}
+
+/// Start casting a spell for the player. Ranged spells go to targeting first
+pub fn begin_cast(world: &mut World, spell: Entity) -> RunState{
+    let player_entity = *world.fetch::<Entity>();
+    let mana_cost = world.read_storage::<Spell>().get(spell).map(|s| s.mana_cost).unwrap_or(0);
+    let mana = world.read_storage::<Mana>().get(player_entity).map(|m| m.current).unwrap_or(0);
+
+    if mana < mana_cost{
+        let name = world.read_storage::<Name>().get(spell).unwrap().name.clone();
+        world.fetch_mut::<GameLog>().add(LogEntry::new().text("You don't have enough mana to cast ").name(&name).text("."));
+        return RunState::AwaitingInput;
+    }
+
+    if let Some(ranged) = world.read_storage::<Ranged>().get(spell){
+        return RunState::ShowTargetting { range: ranged.range, item: spell, cursor: None };
+    }
+
+    world.write_storage::<WantsToCastSpell>().insert(player_entity, WantsToCastSpell{ spell, target: None })
+        .expect("Could not insert intent to cast spell.");
+    RunState::PlayerTurn
+}
+
+/// Pays for a spell and hands it to the item use system, which applies its effects the same way it does for items
+pub struct SpellCastSystem{ }
+
+impl<'a> System<'a> for SpellCastSystem{
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Spell>,
+                        WriteStorage<'a, Mana>,
+                        WriteStorage<'a, WantsToCastSpell>,
+                        WriteStorage<'a, WantsToUseItem>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, player_entity, mut gamelog, names, spells, mut mana, mut wants_to_cast, mut wants_to_use_item) = data;
+
+        for (entity, cast) in (&entities, &wants_to_cast).join(){
+            let spell = match spells.get(cast.spell){
+                Some(spell) => spell,
+                None => continue,
+            };
+            let caster_mana = match mana.get_mut(entity){
+                Some(caster_mana) => caster_mana,
+                None => continue,
+            };
+
+            let spell_name = &names.get(cast.spell).unwrap().name;
+            if caster_mana.current < spell.mana_cost{
+                if entity == *player_entity{
+                    gamelog.add(LogEntry::new().text("You don't have enough mana to cast ").name(spell_name).text("."));
+                }
+                continue;
+            }
+
+            caster_mana.current -= spell.mana_cost;
+            wants_to_use_item.insert(entity, WantsToUseItem{ item: cast.spell, target: cast.target })
+                .expect("Could not insert intent to use spell.");
+            if entity == *player_entity{
+                gamelog.add(LogEntry::new().text("You cast ").name(spell_name).text("."));
+            }
+        }
+
+        wants_to_cast.clear();
+    }
+}
+
+pub struct ManaRegenSystem{ }
+
+impl<'a> System<'a> for ManaRegenSystem{
+    type SystemData = ( ReadExpect<'a, RunState>,
+                        WriteStorage<'a, Mana>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (run_state, mut mana) = data;
+
+        // one tick per turn
+        if *run_state != RunState::MonsterTurn{
+            return;
+        }
+
+        for mana in (&mut mana).join(){
+            if mana.current >= mana.max{
+                mana.regen_timer = 0;
+                continue;
+            }
+
+            mana.regen_timer += 1;
+            if mana.regen_timer >= mana.regen_turns{
+                mana.regen_timer = 0;
+                mana.current += 1;
+            }
+        }
+    }
+}
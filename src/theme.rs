@@ -0,0 +1,50 @@
+use rltk::{RGB, FontCharType};
+use serde::{Serialize, Deserialize};
+
+/// The last level of the dungeon
+pub const FINAL_DEPTH: i32 = 9;
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum LevelTheme{
+    Town,
+    Crypt,
+    Caves,
+    Forest,
+    Fortress,
+    Lair,
+}
+
+impl LevelTheme{
+    /// Which theme a depth uses. The descent goes crypt, caves, forest, fortress and ends in the lair
+    pub fn for_depth(map_depth: i32) -> LevelTheme{
+        match map_depth{
+            i32::MIN..=0 => LevelTheme::Town,
+            1..=2 => LevelTheme::Crypt,
+            3..=4 => LevelTheme::Caves,
+            5..=6 => LevelTheme::Forest,
+            7..=8 => LevelTheme::Fortress,
+            _ => LevelTheme::Lair,
+        }
+    }
+
+    pub fn wall_glyph(&self) -> (FontCharType, RGB){
+        match self{
+            LevelTheme::Town => (rltk::to_cp437('#'), RGB::from_f32(0.0, 1.0, 0.0)),
+            LevelTheme::Crypt => (rltk::to_cp437('#'), RGB::from_f32(0.6, 0.6, 0.6)),
+            LevelTheme::Caves => (rltk::to_cp437('▒'), RGB::from_f32(0.55, 0.35, 0.2)),
+            LevelTheme::Forest => (rltk::to_cp437('♠'), RGB::from_f32(0.0, 0.6, 0.0)),
+            LevelTheme::Fortress => (rltk::to_cp437('#'), RGB::from_f32(0.7, 0.2, 0.2)),
+            LevelTheme::Lair => (rltk::to_cp437('#'), RGB::from_f32(0.6, 0.0, 0.8)),
+        }
+    }
+
+    pub fn floor_glyph(&self) -> (FontCharType, RGB){
+        match self{
+            LevelTheme::Town | LevelTheme::Fortress => (rltk::to_cp437('.'), RGB::from_f32(0.5, 0.5, 0.5)),
+            LevelTheme::Crypt => (rltk::to_cp437('.'), RGB::from_f32(0.35, 0.35, 0.4)),
+            LevelTheme::Caves => (rltk::to_cp437('.'), RGB::from_f32(0.6, 0.5, 0.35)),
+            LevelTheme::Forest => (rltk::to_cp437('.'), RGB::from_f32(0.4, 0.3, 0.1)),
+            LevelTheme::Lair => (rltk::to_cp437('.'), RGB::from_f32(0.5, 0.1, 0.1)),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use specs::prelude::*;
+
+use crate::{EntityMoved, EntryTrigger, Hidden, Map, Name, Position, GameLog, LogEntry, AreaOfEffect, EffectQueue, EffectType, aimed_targets};
+
+pub struct TriggerSystem{ }
+
+impl<'a> System<'a> for TriggerSystem{
+    type SystemData = ( ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, EntityMoved>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, EntryTrigger>,
+                        WriteStorage<'a, Hidden>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, AreaOfEffect>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteExpect<'a, GameLog>,
+                    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, entities, mut entity_moved, positions, entry_triggers, mut hidden, names, area_of_effect, mut effect_queue, mut gamelog) = data;
+
+        for (entity, _moved, pos) in (&entities, &entity_moved, &positions).join(){
+            let idx = map.xy_idx(pos.x, pos.y);
+
+            for trigger in map.tile_content[idx].iter(){
+                if entity == *trigger || entry_triggers.get(*trigger).is_none(){
+                    continue;
+                }
+
+                // the trap goes off on its own tile with the same effects an item would have
+                let targets = aimed_targets(&map, rltk::Point::new(pos.x, pos.y), area_of_effect.get(*trigger));
+                effect_queue.add(Some(*trigger), Some(*trigger), EffectType::ItemUse{ item: *trigger }, targets);
+
+                // a triggered trap is no secret anymore
+                hidden.remove(*trigger);
+
+                if let (Some(victim), Some(trap)) = (names.get(entity), names.get(*trigger)){
+                    gamelog.add(LogEntry::new().name(&victim.name).text(" triggers a ").name(&trap.name).warning("!"));
+                }
+            }
+        }
+
+        entity_moved.clear();
+    }
+}